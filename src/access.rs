@@ -0,0 +1,110 @@
+use crate::config::AccessConfig;
+use crate::platform::IncomingMessage;
+
+/// メッセージがトリガーの許可/拒否リストを満たすかどうかを判定する関数
+///
+/// 判定ルール:
+/// 1. チャンネル・カテゴリ・ロールのいずれかが拒否リストに含まれていれば拒否
+/// 2. チャンネルかカテゴリの許可リストが設定されていれば、どちらかに含まれている必要がある
+/// 3. ロールの許可リストが設定されていれば、いずれかのロールを持っている必要がある
+///
+/// # 引数
+/// * `config` - トリガーの許可/拒否リスト
+/// * `msg` - 受信したメッセージ
+///
+/// # 戻り値
+/// * `true` - トリガーを実行してよい場合
+/// * `false` - トリガーを実行しない場合
+pub fn is_allowed(config: &AccessConfig, msg: &IncomingMessage) -> bool {
+    let in_category = |ids: &[u64]| msg.category_id.is_some_and(|id| ids.contains(&id));
+    let has_role = |ids: &[u64]| msg.role_ids.iter().any(|id| ids.contains(id));
+
+    // --- 拒否リスト（許可リストより優先） ---
+    if config.deny_channels.contains(&msg.channel_id)
+        || in_category(&config.deny_categories)
+        || has_role(&config.deny_roles)
+    {
+        return false;
+    }
+
+    // --- 場所（チャンネル・カテゴリ）の許可リスト ---
+    let location_restricted =
+        !config.allow_channels.is_empty() || !config.allow_categories.is_empty();
+    if location_restricted
+        && !config.allow_channels.contains(&msg.channel_id)
+        && !in_category(&config.allow_categories)
+    {
+        return false;
+    }
+
+    // --- ロールの許可リスト ---
+    config.allow_roles.is_empty() || has_role(&config.allow_roles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(channel_id: u64, category_id: Option<u64>, role_ids: &[u64]) -> IncomingMessage {
+        IncomingMessage {
+            id: 1,
+            channel_id,
+            guild_id: Some(100),
+            category_id,
+            role_ids: role_ids.to_vec(),
+            content: "侍".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn empty_config_allows_everything() {
+        let config = AccessConfig::default();
+        assert!(is_allowed(&config, &message(1, None, &[])));
+        assert!(is_allowed(&config, &message(2, Some(3), &[4])));
+    }
+
+    #[test]
+    fn channel_and_category_allow_lists() {
+        let config = AccessConfig {
+            allow_channels: vec![10],
+            allow_categories: vec![20],
+            ..Default::default()
+        };
+        assert!(is_allowed(&config, &message(10, None, &[])));
+        assert!(is_allowed(&config, &message(11, Some(20), &[])));
+        assert!(!is_allowed(&config, &message(11, Some(21), &[])));
+        assert!(!is_allowed(&config, &message(11, None, &[])));
+    }
+
+    #[test]
+    fn role_allow_list() {
+        let config = AccessConfig {
+            allow_roles: vec![7, 8],
+            ..Default::default()
+        };
+        assert!(is_allowed(&config, &message(1, None, &[5, 8])));
+        assert!(!is_allowed(&config, &message(1, None, &[5])));
+        assert!(!is_allowed(&config, &message(1, None, &[])));
+    }
+
+    #[test]
+    fn deny_lists_win_over_allow_lists() {
+        let config = AccessConfig {
+            allow_categories: vec![20],
+            deny_channels: vec![10],
+            deny_roles: vec![9],
+            ..Default::default()
+        };
+        assert!(is_allowed(&config, &message(11, Some(20), &[])));
+        assert!(!is_allowed(&config, &message(10, Some(20), &[])));
+        assert!(!is_allowed(&config, &message(11, Some(20), &[9])));
+
+        let deny_category = AccessConfig {
+            deny_categories: vec![30],
+            ..Default::default()
+        };
+        assert!(!is_allowed(&deny_category, &message(1, Some(30), &[])));
+        assert!(is_allowed(&deny_category, &message(1, Some(31), &[])));
+    }
+}
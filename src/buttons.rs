@@ -0,0 +1,165 @@
+use crate::platform::Button;
+
+/// custom_id の先頭に付ける識別子（他の機能のボタンと区別する）
+const PREFIX: &str = "samurai";
+
+/// 侍の返信に付けるボタンの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonAction {
+    /// 別の侍を引き直す（呼び出した本人のみ）
+    Reroll,
+    /// 説明文を全文表示する
+    Detail,
+    /// お気に入りに追加する
+    Favorite,
+}
+
+impl ButtonAction {
+    /// custom_id に埋め込む名前
+    fn as_str(self) -> &'static str {
+        match self {
+            ButtonAction::Reroll => "reroll",
+            ButtonAction::Detail => "detail",
+            ButtonAction::Favorite => "favorite",
+        }
+    }
+
+    /// ボタンに表示する文言
+    fn label(self) -> &'static str {
+        match self {
+            ButtonAction::Reroll => "もう一回",
+            ButtonAction::Detail => "詳細",
+            ButtonAction::Favorite => "お気に入り",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "reroll" => Some(ButtonAction::Reroll),
+            "detail" => Some(ButtonAction::Detail),
+            "favorite" => Some(ButtonAction::Favorite),
+            _ => None,
+        }
+    }
+}
+
+/// ボタンの custom_id に埋め込む情報
+///
+/// 形式: `samurai:<action>:<entry_id>:<owner_id>:<issued_at>`
+/// # フィールド
+/// * `action` - ボタンの種類
+/// * `entry_id` - 表示中の侍の ID
+/// * `owner_id` - 侍を呼び出したユーザーID
+/// * `issued_at` - ボタンを発行した UNIX 時刻（秒）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonId {
+    pub action: ButtonAction,
+    pub entry_id: u32,
+    pub owner_id: u64,
+    pub issued_at: u64,
+}
+
+impl ButtonId {
+    /// custom_id の文字列に変換する関数
+    pub fn encode(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}",
+            PREFIX,
+            self.action.as_str(),
+            self.entry_id,
+            self.owner_id,
+            self.issued_at
+        )
+    }
+
+    /// custom_id の文字列を解析する関数
+    /// # 戻り値
+    /// * `None` - このボットの侍ボタンではない（または壊れている）場合
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.split(':');
+        if parts.next()? != PREFIX {
+            return None;
+        }
+        let id = Self {
+            action: ButtonAction::parse(parts.next()?)?,
+            entry_id: parts.next()?.parse().ok()?,
+            owner_id: parts.next()?.parse().ok()?,
+            issued_at: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(id)
+    }
+
+    /// ボタンの有効期限が切れているかどうかを判定する関数
+    /// # 引数
+    /// * `now` - 現在の UNIX 時刻（秒）
+    /// * `expiry_secs` - 有効期間（秒）
+    pub fn is_expired(&self, now: u64, expiry_secs: u64) -> bool {
+        now.saturating_sub(self.issued_at) > expiry_secs
+    }
+}
+
+/// 侍の返信に付けるボタン一式を作成する関数
+/// # 引数
+/// * `entry_id` - 表示中の侍の ID
+/// * `owner_id` - 侍を呼び出したユーザーID
+/// * `issued_at` - 発行時刻（UNIX 時刻・秒）
+pub fn samurai_buttons(entry_id: u32, owner_id: u64, issued_at: u64) -> Vec<Button> {
+    [
+        ButtonAction::Reroll,
+        ButtonAction::Detail,
+        ButtonAction::Favorite,
+    ]
+    .into_iter()
+    .map(|action| Button {
+        custom_id: ButtonId {
+            action,
+            entry_id,
+            owner_id,
+            issued_at,
+        }
+        .encode(),
+        label: action.label().to_string(),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_id_round_trip() {
+        for button in samurai_buttons(12, 345, 1_700_000_000) {
+            let id = ButtonId::parse(&button.custom_id).unwrap();
+            assert_eq!(id.entry_id, 12);
+            assert_eq!(id.owner_id, 345);
+            assert_eq!(id.issued_at, 1_700_000_000);
+        }
+        assert_eq!(
+            ButtonId::parse("samurai:detail:1:2:3").map(|id| id.action),
+            Some(ButtonAction::Detail)
+        );
+    }
+
+    #[test]
+    fn rejects_foreign_or_broken_ids() {
+        for custom_id in [
+            "",
+            "other:reroll:1:2:3",
+            "samurai:unknown:1:2:3",
+            "samurai:reroll:x:2:3",
+            "samurai:reroll:1:2",
+            "samurai:reroll:1:2:3:4",
+        ] {
+            assert_eq!(ButtonId::parse(custom_id), None, "{}", custom_id);
+        }
+    }
+
+    #[test]
+    fn expires_after_configured_seconds() {
+        let id = ButtonId::parse("samurai:reroll:1:2:100").unwrap();
+        assert!(!id.is_expired(100, 60));
+        assert!(!id.is_expired(160, 60));
+        assert!(id.is_expired(161, 60));
+    }
+}
@@ -1,8 +1,37 @@
 use crate::config::app_config;
+use crate::metrics::METRICS;
 use anyhow::{Context, Result, anyhow};
 use log::debug;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serenity::async_trait;
+use std::time::Instant;
+
+/// チャット応答を生成するバックエンドのトレイト
+///
+/// ハンドラはこのトレイト越しに応答を取得するため、
+/// テストでは Ollama に接続せずにスタブへ差し替えられます。
+#[async_trait]
+pub trait ChatBackend: Send + Sync {
+    /// 過去の会話履歴を含めてチャットを行う
+    /// # 引数
+    /// * `history` - これまでの会話（"user" / "assistant" のメッセージ）
+    /// * `user_input` - ユーザーからの入力メッセージ
+    /// # 戻り値
+    /// * `Ok(String)` - チャットの応答メッセージ
+    /// * `Err(Error)` - エラーが発生した場合
+    async fn chat_with_history(&self, history: &[Message], user_input: &str) -> Result<String>;
+
+    /// 履歴なしでチャットを行う
+    /// # 引数
+    /// * `user_input` - ユーザーからの入力メッセージ
+    /// # 戻り値
+    /// * `Ok(String)` - チャットの応答メッセージ
+    /// * `Err(Error)` - エラーが発生した場合
+    async fn chat_once(&self, user_input: &str) -> Result<String> {
+        self.chat_with_history(&[], user_input).await
+    }
+}
 
 #[derive(Clone)]
 pub struct OllamaChat {
@@ -15,7 +44,7 @@ pub struct OllamaChat {
 /// Ollama チャットクライアントの実装
 /// # メソッド
 /// * `new` - 新しいチャットクライアントを作成する
-/// * `chat_once` - 履歴なしでチャットを行う
+/// * `chat` - メッセージ履歴を渡してチャットを行う
 impl OllamaChat {
     /// 新しい OllamaChat クライアントを作成する関数
     /// # 戻り値
@@ -44,27 +73,6 @@ impl OllamaChat {
         }
     }
 
-    /// 履歴なしでチャットを行う関数
-    /// # 引数
-    /// * `user_input` - ユーザーからの入力メッセージ
-    /// # 戻り値
-    /// * `Ok(String)` - チャットの応答メッセージ
-    /// * `Err(Error)` - エラーが発生した場合
-    pub async fn chat_once(&self, user_input: &str) -> Result<String> {
-        let messages = vec![
-            Message {
-                role: "system".to_string(),
-                content: self.system_prompt.clone(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: user_input.to_string(),
-            },
-        ];
-
-        self.chat(messages).await
-    }
-
     /// チャットを行う関数
     /// # 引数
     /// * `messages` - チャットのメッセージ履歴
@@ -86,6 +94,7 @@ impl OllamaChat {
             url, self.model, req.stream
         );
 
+        let started = Instant::now();
         let resp = self
             .http
             .post(url)
@@ -99,6 +108,10 @@ impl OllamaChat {
             .text()
             .await
             .context("failed to read ollama response body")?;
+        METRICS
+            .ollama_latency
+            .observe(started.elapsed().as_secs_f64());
+        METRICS.ollama_response_bytes.observe(body.len() as f64);
 
         if !status.is_success() {
             return Err(anyhow!(
@@ -151,6 +164,44 @@ impl OllamaChat {
     }
 }
 
+#[async_trait]
+impl ChatBackend for OllamaChat {
+    /// 会話履歴を含めてチャットを行う関数
+    /// # 引数
+    /// * `history` - これまでの会話
+    /// * `user_input` - ユーザーからの入力メッセージ
+    /// # 戻り値
+    /// * `Ok(String)` - チャットの応答メッセージ
+    /// * `Err(Error)` - エラーが発生した場合
+    async fn chat_with_history(&self, history: &[Message], user_input: &str) -> Result<String> {
+        let mut messages = Vec::with_capacity(history.len() + 2);
+        messages.push(Message {
+            role: "system".to_string(),
+            content: self.system_prompt.clone(),
+        });
+        messages.extend_from_slice(history);
+        messages.push(Message {
+            role: "user".to_string(),
+            content: user_input.to_string(),
+        });
+
+        self.chat(messages).await
+    }
+}
+
+/// Ollama に接続せず、入力をそのまま含めた固定形式の応答を返すスタブ
+///
+/// REPL モードでローカル LLM を起動せずにトリガーの動作を確認するために使用します。
+#[derive(Debug, Clone, Default)]
+pub struct StubChat;
+
+#[async_trait]
+impl ChatBackend for StubChat {
+    async fn chat_with_history(&self, _history: &[Message], user_input: &str) -> Result<String> {
+        Ok(format!("(stub) {}", user_input))
+    }
+}
+
 /// チャットメッセージを表す構造体
 /// # フィールド
 /// * `role` - メッセージの役割 ("system", "user", "assistant")
@@ -207,6 +258,12 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[tokio::test]
+    async fn stub_chat_echoes_input() {
+        let reply = StubChat.chat_once("ザウルス").await.expect("stub chat");
+        assert_eq!(reply, "(stub) ザウルス");
+    }
+
     #[test]
     fn message_serde_roundtrip() {
         let msg = Message {
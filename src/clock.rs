@@ -0,0 +1,33 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 現在時刻を返すトレイト
+///
+/// ハンドラはこのトレイト越しに時刻を取得するため、
+/// テストでは時間帯・曜日の判定を固定した時刻で確認できます。
+pub trait Clock: Send + Sync {
+    /// 現在の UNIX 時刻（秒）を返す
+    fn now(&self) -> u64;
+}
+
+/// システムの時計を使う `Clock` の実装
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// 常に同じ時刻を返す `Clock` の実装（テスト用）
+#[cfg(test)]
+pub struct FixedClock(pub u64);
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,191 @@
+use crate::persist::{self, JsonFile};
+use anyhow::Result;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// ユーザー1人分のコレクション
+/// # フィールド
+/// * `seen` - これまでに引いた侍の ID と回数
+/// * `favorites` - お気に入りの侍の ID
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserCollection {
+    #[serde(default)]
+    pub seen: BTreeMap<u32, u32>,
+    #[serde(default)]
+    pub favorites: BTreeSet<u32>,
+}
+
+impl UserCollection {
+    /// 全侍のうち、引いたことのある侍の割合（%）を返す関数
+    /// # 引数
+    /// * `total` - 侍データの件数
+    pub fn completion_percent(&self, total: usize) -> f64 {
+        if total == 0 {
+            return 0.0;
+        }
+        // データが差し替えられて範囲外になった ID は数えない
+        let seen = self
+            .seen
+            .keys()
+            .filter(|id| (**id as usize) < total)
+            .count();
+        seen as f64 * 100.0 / total as f64
+    }
+}
+
+/// ユーザーごとの侍コレクション（引いた侍とお気に入り）を保持する構造体
+///
+/// 永続化先が設定されていれば、`flush` で JSON ファイルへ書き出します。
+#[derive(Debug, Default)]
+pub struct Collection {
+    users: Mutex<HashMap<u64, UserCollection>>,
+    file: Option<JsonFile>,
+}
+
+impl Collection {
+    /// 新しい Collection を作成する関数（メモリ上のみ）
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ファイルに永続化する Collection を作成する関数
+    ///
+    /// ファイルが存在すれば前回の内容を読み込みます（存在しなければ空の状態から始めます）。
+    /// # 引数
+    /// * `path` - 永続化先の JSON ファイルのパス
+    /// # 戻り値
+    /// * `Ok(Collection)` - 読み込みに成功した場合
+    /// * `Err(anyhow::Error)` - ファイルの読み込み・解析に失敗した場合
+    pub fn with_persistence(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Ok(Self {
+            users: Mutex::new(persist::load_json(path)?),
+            file: Some(JsonFile::new(path)),
+        })
+    }
+
+    /// 侍を引いたことを記録する関数
+    /// # 引数
+    /// * `user_id` - 引いたユーザーID
+    /// * `entry_id` - 引いた侍の ID
+    pub fn record_draw(&self, user_id: u64, entry_id: u32) {
+        let Ok(mut users) = self.users.lock() else {
+            return;
+        };
+        *users
+            .entry(user_id)
+            .or_default()
+            .seen
+            .entry(entry_id)
+            .or_default() += 1;
+        self.mark_dirty();
+    }
+
+    /// お気に入りに追加する関数
+    /// # 引数
+    /// * `user_id` - ユーザーID
+    /// * `entry_id` - 侍の ID
+    /// # 戻り値
+    /// * `true` - 新しく追加した場合
+    /// * `false` - すでに追加済みの場合
+    pub fn add_favorite(&self, user_id: u64, entry_id: u32) -> bool {
+        let Ok(mut users) = self.users.lock() else {
+            return false;
+        };
+        let added = users.entry(user_id).or_default().favorites.insert(entry_id);
+        if added {
+            self.mark_dirty();
+        }
+        added
+    }
+
+    /// ユーザーのコレクションを取得する関数
+    /// # 引数
+    /// * `user_id` - ユーザーID
+    pub fn get(&self, user_id: u64) -> UserCollection {
+        self.users
+            .lock()
+            .ok()
+            .and_then(|users| users.get(&user_id).cloned())
+            .unwrap_or_default()
+    }
+
+    /// 変更があったことを記録する（書き出しは `flush` で行う）
+    fn mark_dirty(&self) {
+        if let Some(file) = &self.file {
+            file.mark_dirty();
+        }
+    }
+
+    /// 変更があれば、現在の内容をファイルに書き出す関数（ブロッキング I/O を行う）
+    pub fn flush(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let snapshot = || {
+            self.users
+                .lock()
+                .map(|users| users.clone())
+                .unwrap_or_default()
+        };
+        if let Err(e) = file.flush(snapshot) {
+            error!("{:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_each_favorite_once_per_user() {
+        let collection = Collection::new();
+        assert!(collection.add_favorite(1, 5));
+        assert!(!collection.add_favorite(1, 5));
+        assert!(collection.add_favorite(1, 2));
+        assert!(collection.add_favorite(2, 5));
+
+        let favorites: Vec<u32> = collection.get(1).favorites.into_iter().collect();
+        assert_eq!(favorites, vec![2, 5]);
+        assert_eq!(collection.get(2).favorites.len(), 1);
+        assert!(collection.get(3).favorites.is_empty());
+    }
+
+    #[test]
+    fn counts_draws_and_completion() {
+        let collection = Collection::new();
+        collection.record_draw(1, 0);
+        collection.record_draw(1, 0);
+        collection.record_draw(1, 3);
+        collection.record_draw(1, 99);
+
+        let user = collection.get(1);
+        assert_eq!(user.seen.get(&0), Some(&2));
+        assert_eq!(user.seen.get(&3), Some(&1));
+        assert_eq!(user.completion_percent(4), 50.0);
+        assert_eq!(UserCollection::default().completion_percent(0), 0.0);
+    }
+
+    #[test]
+    fn persists_across_restarts() {
+        let path =
+            std::env::temp_dir().join(format!("kasu-collection-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let collection = Collection::with_persistence(&path).unwrap();
+        collection.record_draw(7, 1);
+        collection.add_favorite(7, 1);
+        // 書き出すまではファイルに反映しない
+        assert!(!path.exists());
+        collection.flush();
+
+        let restored = Collection::with_persistence(&path).unwrap();
+        assert_eq!(restored.get(7), collection.get(7));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,384 @@
+use crate::daily;
+use crate::detect::Trigger;
+use crate::handler::HandlerDeps;
+use crate::platform::{CommandInvocation, Embed, InteractionResponder};
+use crate::stats::Period;
+use crate::template;
+use log::{error, info};
+
+/// 一覧に表示する侍の最大件数（埋め込みの文字数制限に収めるため）
+const MAX_LISTED: usize = 20;
+/// コレクション・お気に入りの埋め込みの色
+const COLLECTION_COLOR: u32 = 0x8B0000;
+/// ランキングに表示する最大人数
+const LEADERBOARD_SIZE: usize = 10;
+/// ランキングの埋め込みの色
+const LEADERBOARD_COLOR: u32 = 0xDAA520;
+/// ランキングの上位3人に付けるメダル
+const MEDALS: [&str; 3] = ["🥇", "🥈", "🥉"];
+
+/// スラッシュコマンドの定義
+/// # フィールド
+/// * `name` - コマンド名
+/// * `description` - コマンドの説明
+/// * `subcommands` - サブコマンド名と説明の組
+/// * `options` - 文字列オプション（すべて省略可能）
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub subcommands: &'static [(&'static str, &'static str)],
+    pub options: &'static [OptionSpec],
+}
+
+/// スラッシュコマンドの文字列オプションの定義
+/// # フィールド
+/// * `name` - オプション名
+/// * `description` - オプションの説明
+/// * `choices` - 選択肢の表示名と値の組
+#[derive(Debug)]
+pub struct OptionSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub choices: &'static [(&'static str, &'static str)],
+}
+
+/// 起動時に登録するスラッシュコマンド
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "samurai",
+        description: "侍に関するコマンド",
+        subcommands: &[
+            ("collection", "これまでに引いた侍と達成率を表示します"),
+            ("favorites", "お気に入りの侍を表示します"),
+            ("today", "今日の侍を表示します"),
+        ],
+        options: &[],
+    },
+    CommandSpec {
+        name: "leaderboard",
+        description: "このサーバーでトリガーを多く送った人のランキングを表示します",
+        subcommands: &[],
+        options: &[
+            OptionSpec {
+                name: "period",
+                description: "集計期間（省略時は累計）",
+                choices: &[("今日", "daily"), ("今週", "weekly"), ("累計", "all")],
+            },
+            OptionSpec {
+                name: "trigger",
+                description: "対象のトリガー（省略時はすべて）",
+                choices: &[("侍", "samurai"), ("ザウルス", "zaurus")],
+            },
+        ],
+    },
+];
+
+/// スラッシュコマンドが呼び出されたときの処理（コマンド名に応じて振り分ける）
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `responder` - インタラクションへの応答先
+/// * `cmd` - 呼び出されたコマンド
+pub async fn handle_command(
+    deps: &HandlerDeps,
+    responder: &dyn InteractionResponder,
+    cmd: &CommandInvocation,
+) {
+    info!(
+        "Received command /{} {} from user: {}",
+        cmd.name,
+        cmd.subcommand.as_deref().unwrap_or(""),
+        cmd.user_name
+    );
+    let result = match (cmd.name.as_str(), cmd.subcommand.as_deref()) {
+        ("samurai", Some("collection")) => {
+            responder.respond_embed(&collection_embed(deps, cmd)).await
+        }
+        ("samurai", Some("favorites")) => {
+            responder.respond_embed(&favorites_embed(deps, cmd)).await
+        }
+        ("samurai", Some("today")) => {
+            match daily::today_entry(&deps.samurai_entries, deps.clock.now()) {
+                Some(entry) => {
+                    responder
+                        .respond_embed(&daily::daily_embed(deps, entry))
+                        .await
+                }
+                None => {
+                    responder
+                        .respond_ephemeral("侍が見つかりませんでした。")
+                        .await
+                }
+            }
+        }
+        ("leaderboard", _) => match cmd.guild_id {
+            Some(guild_id) => {
+                let embed = leaderboard_embed(deps, cmd, guild_id);
+                responder.respond_embed(&embed).await
+            }
+            None => {
+                responder
+                    .respond_ephemeral("ランキングはサーバー内でのみ使えます。")
+                    .await
+            }
+        },
+        _ => responder.respond_ephemeral("不明なコマンドです。").await,
+    };
+    if let Err(e) = result {
+        error!("{:?}", e);
+    }
+}
+
+/// `/samurai collection`: 引いた侍の一覧と達成率
+fn collection_embed(deps: &HandlerDeps, cmd: &CommandInvocation) -> Embed {
+    let user = deps.collection.get(cmd.user_id);
+    let total = deps.samurai_entries.len();
+    let seen: Vec<(u32, u32)> = user
+        .seen
+        .iter()
+        .filter(|(id, _)| (**id as usize) < total)
+        .map(|(id, count)| (*id, *count))
+        .collect();
+
+    let mut lines = vec![format!(
+        "達成率: {}/{} ({:.1}%)",
+        seen.len(),
+        total,
+        user.completion_percent(total)
+    )];
+    if seen.is_empty() {
+        lines.push("まだ侍を引いていません。".to_string());
+    } else {
+        lines.push(String::new());
+        lines.extend(seen.iter().take(MAX_LISTED).map(|(id, count)| {
+            let star = if user.favorites.contains(id) {
+                "⭐ "
+            } else {
+                ""
+            };
+            format!("{}`#{}` {} ×{}", star, id, entry_name(deps, *id), count)
+        }));
+        if seen.len() > MAX_LISTED {
+            lines.push(format!("…ほか {} 人", seen.len() - MAX_LISTED));
+        }
+    }
+
+    Embed {
+        title: format!("{} さんの侍コレクション", display_name(cmd)),
+        description: lines.join("\n"),
+        color: COLLECTION_COLOR,
+        footer: Some(format!("お気に入り: {} 人", user.favorites.len())),
+        ..Default::default()
+    }
+}
+
+/// `/samurai favorites`: お気に入りの侍の一覧
+fn favorites_embed(deps: &HandlerDeps, cmd: &CommandInvocation) -> Embed {
+    let user = deps.collection.get(cmd.user_id);
+    let mut lines: Vec<String> = user
+        .favorites
+        .iter()
+        .take(MAX_LISTED)
+        .map(|id| format!("⭐ `#{}` {}", id, entry_name(deps, *id)))
+        .collect();
+    if lines.is_empty() {
+        lines.push(
+            "お気に入りはまだありません。侍の返信の「お気に入り」ボタンで追加できます。"
+                .to_string(),
+        );
+    } else if user.favorites.len() > MAX_LISTED {
+        lines.push(format!("…ほか {} 人", user.favorites.len() - MAX_LISTED));
+    }
+
+    Embed {
+        title: format!("{} さんのお気に入り", display_name(cmd)),
+        description: lines.join("\n"),
+        color: COLLECTION_COLOR,
+        ..Default::default()
+    }
+}
+
+/// `/leaderboard`: サーバー内でトリガーを多く送った人のランキング
+fn leaderboard_embed(deps: &HandlerDeps, cmd: &CommandInvocation, guild_id: u64) -> Embed {
+    let period = cmd
+        .option("period")
+        .and_then(Period::parse)
+        .unwrap_or(Period::AllTime);
+    let trigger = cmd.option("trigger").and_then(|name| match name {
+        "samurai" => Some(Trigger::Samurai),
+        "zaurus" => Some(Trigger::Zaurus),
+        _ => None,
+    });
+    let ranking = deps.stats.leaderboard(
+        guild_id,
+        period,
+        trigger,
+        deps.clock.now(),
+        LEADERBOARD_SIZE,
+    );
+
+    let lines: Vec<String> = ranking
+        .iter()
+        .enumerate()
+        .map(|(rank, (user_id, count))| {
+            let place = MEDALS
+                .get(rank)
+                .map(|medal| medal.to_string())
+                .unwrap_or_else(|| format!("{}.", rank + 1));
+            format!("{} <@{}> — {} 回", place, user_id, count)
+        })
+        .collect();
+    let description = if lines.is_empty() {
+        "まだ記録がありません。".to_string()
+    } else {
+        lines.join("\n")
+    };
+    let target = match trigger {
+        Some(Trigger::Samurai) => "侍",
+        Some(Trigger::Zaurus) => "ザウルス",
+        None => "トリガー",
+    };
+
+    Embed {
+        title: format!("{}ランキング（{}）", target, period.label()),
+        description,
+        color: LEADERBOARD_COLOR,
+        ..Default::default()
+    }
+}
+
+/// 侍の ID から表示名を返す（データにない ID は `?` にする）
+fn entry_name(deps: &HandlerDeps, id: u32) -> String {
+    deps.samurai_entries
+        .get(id as usize)
+        .map(|entry| template::escape_markdown(&entry.name))
+        .unwrap_or_else(|| "?".to_string())
+}
+
+/// 埋め込みのタイトルに使うユーザー名
+fn display_name(cmd: &CommandInvocation) -> String {
+    template::escape_markdown(&cmd.user_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::{FixedChat, NOW, deps};
+    use crate::platform::{RecordedAction, RecordingPlatform};
+
+    fn command(subcommand: &str) -> CommandInvocation {
+        CommandInvocation {
+            name: "samurai".to_string(),
+            subcommand: Some(subcommand.to_string()),
+            channel_id: 20,
+            guild_id: Some(30),
+            user_id: 40,
+            user_name: "user_1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn responded_embed(platform: &RecordingPlatform) -> Embed {
+        match platform.take_actions().as_slice() {
+            [RecordedAction::RespondEmbed { embed }] => embed.clone(),
+            other => panic!("unexpected actions: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn collection_shows_completion_and_draws() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+
+        handle_command(&deps, &platform, &command("collection")).await;
+        let embed = responded_embed(&platform);
+        assert_eq!(embed.title, r"user\_1 さんの侍コレクション");
+        assert!(embed.description.starts_with("達成率: 0/1 (0.0%)"));
+
+        deps.collection.record_draw(40, 0);
+        deps.collection.record_draw(40, 0);
+        deps.collection.add_favorite(40, 0);
+        handle_command(&deps, &platform, &command("collection")).await;
+        let embed = responded_embed(&platform);
+        assert_eq!(
+            embed.description,
+            "達成率: 1/1 (100.0%)\n\n⭐ `#0` テスト侍 ×2"
+        );
+        assert_eq!(embed.footer.as_deref(), Some("お気に入り: 1 人"));
+    }
+
+    #[tokio::test]
+    async fn favorites_lists_entries() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+
+        handle_command(&deps, &platform, &command("favorites")).await;
+        assert!(
+            responded_embed(&platform)
+                .description
+                .starts_with("お気に入りはまだありません。")
+        );
+
+        deps.collection.add_favorite(40, 0);
+        handle_command(&deps, &platform, &command("favorites")).await;
+        assert_eq!(responded_embed(&platform).description, "⭐ `#0` テスト侍");
+    }
+
+    #[tokio::test]
+    async fn today_returns_daily_samurai() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+        handle_command(&deps, &platform, &command("today")).await;
+        assert_eq!(responded_embed(&platform).title, "今日の侍: テスト侍");
+    }
+
+    #[tokio::test]
+    async fn leaderboard_ranks_guild_members() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+        let leaderboard = |options: &[(&str, &str)], guild_id| CommandInvocation {
+            name: "leaderboard".to_string(),
+            options: options
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            guild_id,
+            ..Default::default()
+        };
+
+        handle_command(&deps, &platform, &leaderboard(&[], Some(30))).await;
+        let embed = responded_embed(&platform);
+        assert_eq!(embed.title, "トリガーランキング（累計）");
+        assert_eq!(embed.description, "まだ記録がありません。");
+
+        for user_id in [1, 2, 2, 3, 4, 4, 4] {
+            deps.stats.record(30, user_id, Trigger::Samurai, NOW);
+        }
+        deps.stats.record(30, 1, Trigger::Zaurus, NOW);
+        let options = [("period", "daily"), ("trigger", "samurai")];
+        handle_command(&deps, &platform, &leaderboard(&options, Some(30))).await;
+        let embed = responded_embed(&platform);
+        assert_eq!(embed.title, "侍ランキング（今日）");
+        assert_eq!(
+            embed.description,
+            "🥇 <@4> — 3 回\n🥈 <@2> — 2 回\n🥉 <@1> — 1 回\n4. <@3> — 1 回"
+        );
+
+        handle_command(&deps, &platform, &leaderboard(&[], None)).await;
+        assert!(matches!(
+            platform.take_actions().as_slice(),
+            [RecordedAction::Ephemeral { .. }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_commands_get_ephemeral_reply() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+        handle_command(&deps, &platform, &command("unknown")).await;
+        assert!(matches!(
+            platform.take_actions().as_slice(),
+            [RecordedAction::Ephemeral { .. }]
+        ));
+    }
+}
@@ -1,7 +1,10 @@
+use crate::detect::Trigger;
 use anyhow::{Context, Result, anyhow};
 use config::{Config, File};
 use once_cell::sync::OnceCell;
-use serde::Deserialize;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// アプリケーション設定を保持する構造体
@@ -13,11 +16,15 @@ pub struct AppConfig {
     /// Discord ボットのトークン
     pub discord_token: String,
 
-    /// 侍データのCSVファイルパス
+    /// 侍データのファイルパス
+    ///
+    /// CSV のほか、TSV・JSON・TOML・YAML も読み込めます（形式は拡張子から判定）。
+    /// 以前の設定名 `samurai_csv_path` でも指定できます。
     ///
     /// 読み込み元:
     /// - config.toml
-    pub samurai_csv_path: String,
+    #[serde(alias = "samurai_csv_path")]
+    pub samurai_data_path: String,
 
     /// ollama サーバーのベースURL
     ///
@@ -47,6 +54,900 @@ pub struct AppConfig {
     /// 例:
     /// - `"prompts/system_prompt.txt"`
     pub default_system_prompt_path: String,
+
+    /// シャットダウン時に処理中のメッセージを待つ最大秒数
+    ///
+    /// 読み込み元:
+    /// - config.toml（省略時は 10 秒）
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+
+    /// ヘルスチェック用 HTTP サーバーの待ち受けアドレス
+    ///
+    /// 読み込み元:
+    /// - config.toml（省略時はサーバーを起動しない）
+    ///
+    /// 例:
+    /// - `"127.0.0.1:8080"`
+    #[serde(default)]
+    pub health_addr: Option<String>,
+
+    /// 元メッセージとボットの返信の対応を保存するファイルのパス
+    ///
+    /// 読み込み元:
+    /// - config.toml（省略時はメモリ上のみで保持し、再起動すると忘れる）
+    ///
+    /// 例:
+    /// - `"replies.json"`
+    #[serde(default)]
+    pub replies_path: Option<String>,
+
+    /// ユーザーごとの侍コレクション（引いた侍とお気に入り）を保存するファイルのパス
+    ///
+    /// 読み込み元:
+    /// - config.toml（省略時はメモリ上のみで保持し、再起動すると忘れる）
+    ///
+    /// 例:
+    /// - `"collection.json"`
+    #[serde(default)]
+    pub collection_path: Option<String>,
+
+    /// ランキング用のトリガー回数を保存するファイルのパス
+    ///
+    /// 読み込み元:
+    /// - config.toml（省略時はメモリ上のみで保持し、再起動すると忘れる）
+    ///
+    /// 例:
+    /// - `"stats.json"`
+    #[serde(default)]
+    pub stats_path: Option<String>,
+
+    /// 乱数のシード（侍の抽選と応答する確率の判定に使う）
+    ///
+    /// 指定すると起動するたびに同じ順で侍が選ばれるため、不具合の再現に使えます。
+    ///
+    /// 読み込み元:
+    /// - config.toml（省略時は起動ごとに OS の乱数でシードする）
+    ///
+    /// 例:
+    /// - `42`
+    #[serde(default)]
+    pub random_seed: Option<u64>,
+
+    /// トリガーごとの動作設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[triggers.samurai]` / `[triggers.zaurus]`（省略時はすべてデフォルト）
+    #[serde(default)]
+    pub triggers: TriggersConfig,
+
+    /// ダイレクトメッセージ（DM）モードの設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[dm]`（省略時は DM に応答しない）
+    #[serde(default)]
+    pub dm: DmConfig,
+
+    /// メッセージ編集時の動作設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[edits]`（省略時はボットの返信をそのまま残す）
+    #[serde(default)]
+    pub edits: EditsConfig,
+
+    /// 侍の返信の表示設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[samurai_reply]`（省略時は埋め込みで返信する）
+    #[serde(default)]
+    pub samurai_reply: SamuraiReplyConfig,
+
+    /// 返信テンプレートの設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[templates]`（省略時は従来どおりの形式で返信する）
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+
+    /// 侍の返信に付けるボタンの設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[buttons]`（省略時は埋め込みの返信にボタンを付ける）
+    #[serde(default)]
+    pub buttons: ButtonsConfig,
+
+    /// 「今日の侍」の定期投稿の設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[daily]`（省略時は投稿しない）
+    #[serde(default)]
+    pub daily: DailyConfig,
+
+    /// 侍データのファイル形式と列名の設定
+    ///
+    /// 読み込み元:
+    /// - config.toml の `[dataset]`（省略時は拡張子から形式を判定し、英語の列名を使う）
+    #[serde(default)]
+    pub dataset: DatasetConfig,
+}
+
+/// 侍データのファイル形式と列名の設定
+///
+/// 例:
+/// ```toml
+/// [dataset]
+/// format = "tsv"
+///
+/// [dataset.columns]
+/// name = "名前"
+/// description = "説明"
+/// ```
+/// # フィールド
+/// * `format` - ファイル形式（省略時は拡張子から判定し、不明な拡張子は CSV とみなす）
+/// * `columns` - 侍データの各項目に対応する列名（JSON・TOML・YAML ではフィールド名）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DatasetConfig {
+    #[serde(default)]
+    pub format: Option<DatasetFormat>,
+    #[serde(default)]
+    pub columns: DatasetColumns,
+}
+
+/// 侍データのファイル形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetFormat {
+    Csv,
+    Tsv,
+    Json,
+    Toml,
+    Yaml,
+}
+
+/// 侍データの各項目に対応する列名・フィールド名
+/// # フィールド
+/// * `name` - 名前（必須、既定 `"Name"`）
+/// * `description` - 説明（必須、既定 `"Description"`）
+/// * `rarity` - レアリティ（任意、既定 `"Rarity"`）
+/// * `thumbnail` - サムネイル画像の URL（任意、既定 `"Thumbnail"`）
+/// * `image` - 画像の URL（任意、既定 `"Image"`）
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DatasetColumns {
+    #[serde(default = "default_name_column")]
+    pub name: String,
+    #[serde(default = "default_description_column")]
+    pub description: String,
+    #[serde(default = "default_rarity_column")]
+    pub rarity: String,
+    #[serde(default = "default_thumbnail_column")]
+    pub thumbnail: String,
+    #[serde(default = "default_image_column")]
+    pub image: String,
+}
+
+impl Default for DatasetColumns {
+    fn default() -> Self {
+        Self {
+            name: default_name_column(),
+            description: default_description_column(),
+            rarity: default_rarity_column(),
+            thumbnail: default_thumbnail_column(),
+            image: default_image_column(),
+        }
+    }
+}
+
+fn default_name_column() -> String {
+    "Name".to_string()
+}
+
+fn default_description_column() -> String {
+    "Description".to_string()
+}
+
+fn default_rarity_column() -> String {
+    "Rarity".to_string()
+}
+
+fn default_thumbnail_column() -> String {
+    "Thumbnail".to_string()
+}
+
+fn default_image_column() -> String {
+    "Image".to_string()
+}
+
+/// 「今日の侍」を毎日決まった時刻に投稿する設定
+///
+/// 例:
+/// ```toml
+/// [daily]
+/// channels = [123456789012345678]
+/// time = "09:00"
+/// ```
+/// # フィールド
+/// * `channels` - 投稿先のチャンネルID（空なら投稿しない）
+/// * `time` - 投稿する時刻（日本時間、`"HH:MM"` 形式）
+#[derive(Debug, Clone, Deserialize)]
+pub struct DailyConfig {
+    #[serde(default)]
+    pub channels: Vec<u64>,
+    #[serde(default = "default_daily_time")]
+    pub time: DailyTime,
+}
+
+/// 「今日の侍」を投稿する時刻（`"HH:MM"` 形式、日本時間）
+/// # フィールド
+/// * `secs` - 0時からの秒数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct DailyTime {
+    pub secs: u64,
+}
+
+impl TryFrom<String> for DailyTime {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let secs = crate::daily::parse_time(&value)
+            .with_context(|| format!("Invalid daily post time (expected HH:MM): {}", value))?;
+        Ok(Self { secs })
+    }
+}
+
+impl Default for DailyConfig {
+    fn default() -> Self {
+        Self {
+            channels: Vec::new(),
+            time: default_daily_time(),
+        }
+    }
+}
+
+fn default_daily_time() -> DailyTime {
+    DailyTime { secs: 9 * 60 * 60 }
+}
+
+/// 侍の返信（埋め込み）に付けるボタンの設定
+///
+/// 例:
+/// ```toml
+/// [buttons]
+/// enabled = true
+/// expiry_secs = 900
+/// reroll = { capacity = 3, refill_secs = 600 }
+/// ```
+/// # フィールド
+/// * `enabled` - 「もう一回」「詳細」「お気に入り」ボタンを付けるかどうか
+/// * `expiry_secs` - ボタンを押せる期間（秒）
+/// * `reroll` - ユーザーごとの「もう一回」の回数制限
+#[derive(Debug, Clone, Deserialize)]
+pub struct ButtonsConfig {
+    #[serde(default = "default_buttons_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_buttons_expiry_secs")]
+    pub expiry_secs: u64,
+    #[serde(default = "default_reroll_bucket")]
+    pub reroll: BucketConfig,
+}
+
+impl Default for ButtonsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_buttons_enabled(),
+            expiry_secs: default_buttons_expiry_secs(),
+            reroll: default_reroll_bucket(),
+        }
+    }
+}
+
+fn default_buttons_enabled() -> bool {
+    true
+}
+
+fn default_buttons_expiry_secs() -> u64 {
+    900
+}
+
+fn default_reroll_bucket() -> BucketConfig {
+    BucketConfig {
+        capacity: 3,
+        refill_secs: 600.0,
+    }
+}
+
+/// 返信テンプレートの設定
+///
+/// サーバーごとの設定（`[templates.guilds."<サーバーID>"]`）があればそちらを優先します。
+///
+/// 例:
+/// ```toml
+/// [templates]
+/// samurai = "{author}さん、今日の侍は {name} です！\n{description}"
+///
+/// [templates.guilds."123456789012345678"]
+/// zaurus = "🦖 {reply}"
+/// ```
+/// # フィールド
+/// * `samurai` - 侍の返信のテンプレート（使える値: `{id}` `{name}` `{description}` `{rarity}` `{author}`）
+/// * `zaurus` - ザウルスの返信のテンプレート（使える値: `{reply}` `{author}`）
+/// * `guilds` - サーバーIDごとのテンプレート
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TemplatesConfig {
+    #[serde(default)]
+    pub samurai: Option<String>,
+    #[serde(default)]
+    pub zaurus: Option<String>,
+    #[serde(default)]
+    pub guilds: HashMap<String, GuildTemplates>,
+}
+
+/// サーバーごとの返信テンプレート（未設定のトリガーは全体の設定を使う）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GuildTemplates {
+    #[serde(default)]
+    pub samurai: Option<String>,
+    #[serde(default)]
+    pub zaurus: Option<String>,
+}
+
+impl TemplatesConfig {
+    /// 侍の返信の既定テンプレート
+    pub const DEFAULT_SAMURAI: &str = "{id}: {name}\n{description}";
+    /// ザウルスの返信の既定テンプレート
+    pub const DEFAULT_ZAURUS: &str = "{reply}";
+
+    /// トリガーとサーバーに対応するテンプレートを返す関数
+    /// # 引数
+    /// * `trigger` - 返信するトリガー
+    /// * `guild_id` - サーバーID（DM の場合は `None`）
+    /// # 戻り値
+    /// * `&str` - サーバーごとの設定 → 全体の設定 → 既定値の順で見つかったテンプレート
+    pub fn get(&self, trigger: Trigger, guild_id: Option<u64>) -> &str {
+        let guild = guild_id.and_then(|id| self.guilds.get(&id.to_string()));
+        let (guild_template, global_template, default) = match trigger {
+            Trigger::Samurai => (
+                guild.and_then(|g| g.samurai.as_deref()),
+                self.samurai.as_deref(),
+                Self::DEFAULT_SAMURAI,
+            ),
+            Trigger::Zaurus => (
+                guild.and_then(|g| g.zaurus.as_deref()),
+                self.zaurus.as_deref(),
+                Self::DEFAULT_ZAURUS,
+            ),
+        };
+        guild_template.or(global_template).unwrap_or(default)
+    }
+}
+
+/// 侍の返信の表示設定
+///
+/// 例:
+/// ```toml
+/// [samurai_reply]
+/// style = "embed"
+/// color = 0x8B0000
+///
+/// [samurai_reply.rarity_colors]
+/// SR = 0x9B59B6
+/// SSR = 0xF1C40F
+/// ```
+/// # フィールド
+/// * `style` - 返信の形式（埋め込み / テキスト）
+/// * `color` - レアリティが未設定（または色が未定義）の侍の埋め込みの色
+/// * `rarity_colors` - レアリティごとの埋め込みの色（CSV の `Rarity` 列の値で引く）
+#[derive(Debug, Clone, Deserialize)]
+pub struct SamuraiReplyConfig {
+    #[serde(default)]
+    pub style: ReplyStyle,
+    #[serde(default = "default_samurai_color")]
+    pub color: u32,
+    #[serde(default = "default_rarity_colors")]
+    pub rarity_colors: HashMap<String, u32>,
+}
+
+impl Default for SamuraiReplyConfig {
+    fn default() -> Self {
+        Self {
+            style: ReplyStyle::default(),
+            color: default_samurai_color(),
+            rarity_colors: default_rarity_colors(),
+        }
+    }
+}
+
+impl SamuraiReplyConfig {
+    /// レアリティに対応する埋め込みの色を返す関数
+    /// # 引数
+    /// * `rarity` - 侍のレアリティ
+    pub fn color_for(&self, rarity: Option<&str>) -> u32 {
+        rarity
+            .and_then(|r| self.rarity_colors.get(r))
+            .copied()
+            .unwrap_or(self.color)
+    }
+}
+
+/// 返信の形式
+/// * `Embed` - Discord の埋め込み（タイトル・説明・ID・色・画像）
+/// * `Text` - `"{id}: {name}\n{description}"` 形式のテキスト
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplyStyle {
+    #[default]
+    Embed,
+    Text,
+}
+
+fn default_samurai_color() -> u32 {
+    0x8B0000
+}
+
+fn default_rarity_colors() -> HashMap<String, u32> {
+    [
+        ("N", 0x95A5A6),
+        ("R", 0x3498DB),
+        ("SR", 0x9B59B6),
+        ("SSR", 0xF1C40F),
+    ]
+    .into_iter()
+    .map(|(rarity, color)| (rarity.to_string(), color))
+    .collect()
+}
+
+/// メッセージ編集時の動作設定
+///
+/// 例:
+/// ```toml
+/// [edits]
+/// on_trigger_removed = "edit"
+/// removed_notice = "（元のメッセージが編集されました）"
+/// ```
+/// # フィールド
+/// * `on_trigger_removed` - 編集でトリガーが消えたときのボットの返信の扱い
+/// * `removed_notice` - `on_trigger_removed = "edit"` のときに差し替える文言
+#[derive(Debug, Clone, Deserialize)]
+pub struct EditsConfig {
+    #[serde(default)]
+    pub on_trigger_removed: TriggerRemovedAction,
+    #[serde(default = "default_removed_notice")]
+    pub removed_notice: String,
+}
+
+impl Default for EditsConfig {
+    fn default() -> Self {
+        Self {
+            on_trigger_removed: TriggerRemovedAction::default(),
+            removed_notice: default_removed_notice(),
+        }
+    }
+}
+
+/// 編集でトリガーが消えたときのボットの返信の扱い
+/// * `Keep` - そのまま残す
+/// * `Edit` - `removed_notice` の文言に差し替える
+/// * `Delete` - 削除する
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerRemovedAction {
+    #[default]
+    Keep,
+    Edit,
+    Delete,
+}
+
+fn default_removed_notice() -> String {
+    "（元のメッセージが編集されたため、この返信は取り消されました）".to_string()
+}
+
+/// ダイレクトメッセージ（DM）モードの設定
+///
+/// 例:
+/// ```toml
+/// [dm]
+/// enabled = true
+/// reaction_emoji = "⚔️"
+/// memory_turns = 10
+/// ```
+/// # フィールド
+/// * `enabled` - DM に応答するかどうか
+/// * `reaction_emoji` - 侍検知時に使う Unicode 絵文字（DM ではサーバーの絵文字が使えないため）
+/// * `memory_turns` - ユーザーごとに覚えておくザウルスとの会話の往復数
+#[derive(Debug, Clone, Deserialize)]
+pub struct DmConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_dm_reaction_emoji")]
+    pub reaction_emoji: String,
+    #[serde(default = "default_dm_memory_turns")]
+    pub memory_turns: usize,
+}
+
+impl Default for DmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reaction_emoji: default_dm_reaction_emoji(),
+            memory_turns: default_dm_memory_turns(),
+        }
+    }
+}
+
+fn default_dm_reaction_emoji() -> String {
+    "⚔️".to_string()
+}
+
+fn default_dm_memory_turns() -> usize {
+    10
+}
+
+/// トリガーごとの動作設定をまとめた構造体
+/// # フィールド
+/// * `resolution` - 1つのメッセージで複数のトリガーが一致したときの扱い
+/// * `default_mode` - トリガーごとに `mode` を指定しない場合の検知モード
+/// * `markdown` - 検知の前に取り除く Discord の Markdown 記法の設定
+/// * `samurai` - 「侍」トリガーの設定
+/// * `zaurus` - 「ザウルス」トリガーの設定
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TriggersConfig {
+    #[serde(default)]
+    pub resolution: ResolutionPolicy,
+    #[serde(default)]
+    pub default_mode: MatchMode,
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    #[serde(default)]
+    pub samurai: TriggerConfig,
+    #[serde(default)]
+    pub zaurus: TriggerConfig,
+}
+
+impl TriggersConfig {
+    /// トリガーに対応する設定を返す関数
+    /// # 引数
+    /// * `trigger` - 対象のトリガー
+    /// # 戻り値
+    /// * `&TriggerConfig` - トリガーの設定
+    pub fn get(&self, trigger: Trigger) -> &TriggerConfig {
+        match trigger {
+            Trigger::Samurai => &self.samurai,
+            Trigger::Zaurus => &self.zaurus,
+        }
+    }
+
+    /// トリガーの検知モードを返す関数（未指定なら `default_mode`）
+    /// # 引数
+    /// * `trigger` - 対象のトリガー
+    pub fn mode(&self, trigger: Trigger) -> MatchMode {
+        self.get(trigger).mode.unwrap_or(self.default_mode)
+    }
+}
+
+/// 1つのメッセージで複数のトリガーが一致したときの扱い
+/// * `First` - メッセージ内で最初に現れたトリガーだけを実行する
+/// * `Priority` - `priority` が最も高いトリガーだけを実行する（同じなら侍 → ザウルスの順）
+/// * `All` - 一致したすべてのトリガーを、現れた順に実行する
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolutionPolicy {
+    First,
+    #[default]
+    Priority,
+    All,
+}
+
+/// 検知の前に取り除く Discord の Markdown 記法の設定
+///
+/// コードブロック・インラインコード・引用・URL・カスタム絵文字・メンションは常に取り除きます。
+///
+/// 例:
+/// ```toml
+/// [triggers.markdown]
+/// ignore_spoilers = false  # ネタバレ（`||...||`）の中も検知する
+/// ```
+/// # フィールド
+/// * `ignore_spoilers` - ネタバレの中の文字を検知の対象外にするかどうか
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarkdownConfig {
+    #[serde(default = "default_true")]
+    pub ignore_spoilers: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            ignore_spoilers: true,
+        }
+    }
+}
+
+/// 語句がメッセージのどこにあれば検知するか
+/// * `Anywhere` - どこにあっても検知する（例: 「侍ジャパン」）
+/// * `WordFinal` - 語句の直後が文字・数字でない（末尾・空白・記号・絵文字）場合のみ
+/// * `MessageFinal` - メッセージの末尾（後ろに続く記号・絵文字・空白は無視）の場合のみ
+/// * `LineFinal` - いずれかの行の末尾（同上）の場合のみ
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    #[default]
+    Anywhere,
+    WordFinal,
+    MessageFinal,
+    LineFinal,
+}
+
+/// 1つのトリガーの動作設定
+/// # フィールド
+/// * `cooldown` - クールダウン（レート制限）の設定
+/// * `access` - チャンネル・カテゴリ・ロールの許可/拒否リスト
+/// * `normalize` - 検知前の文字列の正規化
+/// * `mode` - 検知モード（省略時は `triggers.default_mode`）
+/// * `priority` - `resolution = "priority"` のときの優先度（大きいほど優先、既定 0）
+/// * `phrases` - 組み込みの語句に加えて検知する語句（正規化してから比較する）
+/// * `patterns` - 正規化後のメッセージに対して検知する正規表現
+/// * `emojis` - メッセージで使われると検知するカスタム絵文字の名前
+/// * `stickers` - メッセージに付いていると検知するスタンプの名前
+/// * `reactions` - メッセージに付けられると検知するリアクション（カスタム絵文字の名前または Unicode 絵文字）
+/// * `gate` - 応答する確率・時間帯・曜日
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TriggerConfig {
+    #[serde(default)]
+    pub mode: Option<MatchMode>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub phrases: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_patterns")]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub emojis: Vec<String>,
+    #[serde(default)]
+    pub stickers: Vec<String>,
+    #[serde(default)]
+    pub reactions: Vec<String>,
+    #[serde(default)]
+    pub cooldown: CooldownConfig,
+    #[serde(default)]
+    pub access: AccessConfig,
+    #[serde(default)]
+    pub gate: GateConfig,
+    #[serde(default)]
+    pub normalize: NormalizeConfig,
+}
+
+/// トリガーを検知する前に、メッセージと検知する語句の両方に適用する正規化の設定
+///
+/// すべて既定で無効です。必要な規則だけをトリガーごとに有効にします。
+///
+/// 例:
+/// ```toml
+/// [triggers.zaurus.normalize]
+/// nfkc = true              # 「ｻﾞｳﾙｽ」も検知する
+/// strip_whitespace = true  # 「ザ ウ ル ス」も検知する
+/// ```
+/// # フィールド
+/// * `nfkc` - Unicode の NFKC 正規化（互換文字をまとめる）
+/// * `width` - 全角英数字・半角カタカナなどの幅をそろえる（`nfkc` が無効なときのみ意味を持つ）
+/// * `kana` - ひらがなをカタカナにそろえる（「さむらい」と「サムライ」を同一視する）
+///   （侍では、読みの「サムライ」も組み込みの語句として検知する）
+/// * `strip_zero_width` - ゼロ幅スペースなどの見えない文字を取り除く
+/// * `strip_whitespace` - 空白・改行を取り除く
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct NormalizeConfig {
+    #[serde(default)]
+    pub nfkc: bool,
+    #[serde(default)]
+    pub width: bool,
+    #[serde(default)]
+    pub kana: bool,
+    #[serde(default)]
+    pub strip_zero_width: bool,
+    #[serde(default)]
+    pub strip_whitespace: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// チャンネル・カテゴリ・ロールの許可/拒否リスト
+///
+/// 拒否リストは許可リストより優先されます。許可リストが空の場合は制限しません。
+///
+/// 例:
+/// ```toml
+/// [triggers.samurai.access]
+/// allow_channels = [123456789012345678]
+///
+/// [triggers.zaurus.access]
+/// allow_roles = [234567890123456789]
+/// deny_categories = [345678901234567890]
+/// ```
+/// # フィールド
+/// * `allow_channels` / `deny_channels` - チャンネルID
+/// * `allow_categories` / `deny_categories` - カテゴリ（親チャンネル）ID
+/// * `allow_roles` / `deny_roles` - ロールID
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AccessConfig {
+    #[serde(default)]
+    pub allow_channels: Vec<u64>,
+    #[serde(default)]
+    pub deny_channels: Vec<u64>,
+    #[serde(default)]
+    pub allow_categories: Vec<u64>,
+    #[serde(default)]
+    pub deny_categories: Vec<u64>,
+    #[serde(default)]
+    pub allow_roles: Vec<u64>,
+    #[serde(default)]
+    pub deny_roles: Vec<u64>,
+}
+
+/// トリガーが応答する条件（確率・時間帯・曜日）
+///
+/// 時間帯・曜日は日本時間で判定し、すべての条件を満たした場合だけ応答します。
+/// DM でも同じ条件を適用します（DM の会話はザウルスの条件に従います）。
+///
+/// 例:
+/// ```toml
+/// [triggers.samurai.gate]
+/// probability = 0.3
+/// active_hours = "09:00-23:00"
+/// days = ["sat", "sun"]
+/// quiet_hours = ["12:00-13:00"]
+/// ```
+/// # フィールド
+/// * `probability` - 応答する確率（0.0〜1.0、既定 1.0）
+/// * `active_hours` - 応答する時間帯（省略時は終日）
+/// * `days` - 応答する曜日（空の場合は毎日）
+/// * `quiet_hours` - 応答しない時間帯（複数指定可）
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GateConfig {
+    #[serde(
+        default = "default_probability",
+        deserialize_with = "deserialize_probability"
+    )]
+    pub probability: f64,
+    #[serde(default)]
+    pub active_hours: Option<TimeWindow>,
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(default)]
+    pub quiet_hours: Vec<TimeWindow>,
+}
+
+impl Default for GateConfig {
+    fn default() -> Self {
+        Self {
+            probability: default_probability(),
+            active_hours: None,
+            days: Vec::new(),
+            quiet_hours: Vec::new(),
+        }
+    }
+}
+
+fn default_probability() -> f64 {
+    1.0
+}
+
+/// 応答する確率を読み込む（0.0〜1.0 の範囲外であれば設定の読み込みを失敗させる）
+fn deserialize_probability<'de, D>(deserializer: D) -> std::result::Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let probability = f64::deserialize(deserializer)?;
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid gate probability (expected 0.0 to 1.0): {}",
+            probability
+        )));
+    }
+    Ok(probability)
+}
+
+/// 検知する正規表現を読み込む（正しくない正規表現があれば設定の読み込みを失敗させる）
+fn deserialize_patterns<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let patterns = Vec::<String>::deserialize(deserializer)?;
+    for pattern in &patterns {
+        Regex::new(pattern).map_err(|e| {
+            serde::de::Error::custom(format!("Invalid trigger pattern '{}': {}", pattern, e))
+        })?;
+    }
+    Ok(patterns)
+}
+
+/// 1日のうちの時間帯（`"HH:MM-HH:MM"` 形式、終了時刻は含まない）
+///
+/// 終了時刻が開始時刻より前の場合は日をまたぐ時間帯（例: `"23:00-06:00"`）、
+/// 同じ場合は終日を表します。
+/// # フィールド
+/// * `start` / `end` - 0時からの秒数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeWindow {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl TryFrom<String> for TimeWindow {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let invalid = || format!("Invalid gate time window (expected HH:MM-HH:MM): {}", value);
+        let (start, end) = value.split_once('-').with_context(invalid)?;
+        Ok(Self {
+            start: crate::daily::parse_time(start).with_context(invalid)?,
+            end: crate::daily::parse_time(end).with_context(invalid)?,
+        })
+    }
+}
+
+/// 曜日（`"mon"` 〜 `"sun"`、`"月"` 〜 `"日"` でも指定可）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    #[serde(alias = "月")]
+    Mon,
+    #[serde(alias = "火")]
+    Tue,
+    #[serde(alias = "水")]
+    Wed,
+    #[serde(alias = "木")]
+    Thu,
+    #[serde(alias = "金")]
+    Fri,
+    #[serde(alias = "土")]
+    Sat,
+    #[serde(alias = "日")]
+    Sun,
+}
+
+/// クールダウンの設定
+///
+/// ユーザー・チャンネル・サーバーごとにトークンバケットを持ち、
+/// 設定されたすべてのバケットにトークンが残っている場合だけ応答します。
+///
+/// 例:
+/// ```toml
+/// [triggers.samurai.cooldown]
+/// react_while_cooling = true
+/// user = { capacity = 3, refill_secs = 60 }
+/// channel = { capacity = 10, refill_secs = 30 }
+/// ```
+/// # フィールド
+/// * `user` - ユーザーごとのバケット（省略時は制限なし）
+/// * `channel` - チャンネルごとのバケット（省略時は制限なし）
+/// * `guild` - サーバーごとのバケット（省略時は制限なし）
+/// * `react_while_cooling` - クールダウン中もリアクションだけは行うかどうか
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CooldownConfig {
+    #[serde(default)]
+    pub user: Option<BucketConfig>,
+    #[serde(default)]
+    pub channel: Option<BucketConfig>,
+    #[serde(default)]
+    pub guild: Option<BucketConfig>,
+    #[serde(default)]
+    pub react_while_cooling: bool,
+}
+
+/// トークンバケットの設定
+/// # フィールド
+/// * `capacity` - バケットの容量（連続で応答できる回数）
+/// * `refill_secs` - トークンが1つ回復するまでの秒数
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct BucketConfig {
+    pub capacity: u32,
+    pub refill_secs: f64,
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    10
 }
 
 fn build_shared_config() -> Result<Config> {
@@ -96,7 +997,7 @@ impl AppConfig {
         let app_config: Self = config
             .try_deserialize()
             .context(
-                "Failed to deserialize configuration. Make sure config.toml provides discord_token, samurai_csv_path, default_ollama_base_url, default_ollama_model, and default_system_prompt_path",
+                "Failed to deserialize configuration. Make sure config.toml provides discord_token, samurai_data_path, default_ollama_base_url, default_ollama_model, and default_system_prompt_path",
             )?;
         Ok(app_config)
     }
@@ -110,7 +1011,7 @@ mod tests {
     fn load_from_toml_lowercase_keys() {
         let raw_toml = r#"
         discord_token = "token_lower"
-        samurai_csv_path = "/path/lower.csv"
+        samurai_data_path = "/path/lower.csv"
         default_ollama_base_url = "http://127.0.0.1:11434"
         default_ollama_model = "llama3.2:1b"
         default_system_prompt_path = "/path/system_prompt.txt"
@@ -123,12 +1024,339 @@ mod tests {
         let app_config: AppConfig = config.try_deserialize().expect("deserialize AppConfig");
 
         assert_eq!(app_config.discord_token, "token_lower");
-        assert_eq!(app_config.samurai_csv_path, "/path/lower.csv");
+        assert_eq!(app_config.samurai_data_path, "/path/lower.csv");
         assert_eq!(app_config.default_ollama_base_url, "http://127.0.0.1:11434");
         assert_eq!(app_config.default_ollama_model, "llama3.2:1b");
         assert_eq!(
             app_config.default_system_prompt_path,
             "/path/system_prompt.txt"
         );
+        assert_eq!(app_config.shutdown_timeout_secs, 10);
+        assert_eq!(app_config.health_addr, None);
+        assert_eq!(app_config.replies_path, None);
+        assert_eq!(app_config.collection_path, None);
+        assert_eq!(app_config.stats_path, None);
+        assert_eq!(app_config.random_seed, None);
+        assert_eq!(app_config.dataset.format, None);
+        assert_eq!(app_config.dataset.columns, DatasetColumns::default());
+        assert!(app_config.daily.channels.is_empty());
+        assert_eq!(app_config.daily.time.secs, 9 * 60 * 60);
+        assert!(app_config.triggers.samurai.cooldown.user.is_none());
+        assert_eq!(
+            app_config.triggers.mode(Trigger::Samurai),
+            MatchMode::Anywhere
+        );
+        assert!(app_config.triggers.markdown.ignore_spoilers);
+        assert_eq!(app_config.triggers.resolution, ResolutionPolicy::Priority);
+        assert!(!app_config.dm.enabled);
+        assert_eq!(app_config.dm.reaction_emoji, "⚔️");
+        assert_eq!(app_config.dm.memory_turns, 10);
+        assert_eq!(
+            app_config.edits.on_trigger_removed,
+            TriggerRemovedAction::Keep
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_time_windows() {
+        for value in ["09:00", "9-18", "09:00-24:00", "ab:cd-01:00"] {
+            assert!(
+                TimeWindow::try_from(value.to_string()).is_err(),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn load_legacy_samurai_csv_path() {
+        let raw_toml = r#"
+        discord_token = "token"
+        samurai_csv_path = "/path/legacy.csv"
+        default_ollama_base_url = "http://127.0.0.1:11434"
+        default_ollama_model = "llama3.2:1b"
+        default_system_prompt_path = "/path/system_prompt.txt"
+        "#;
+
+        let config = Config::builder()
+            .add_source(File::from_str(raw_toml, config::FileFormat::Toml))
+            .build()
+            .expect("build config");
+        let app_config: AppConfig = config.try_deserialize().expect("deserialize AppConfig");
+        assert_eq!(app_config.samurai_data_path, "/path/legacy.csv");
+    }
+
+    /// 必須の設定に `extra` を加えた TOML から設定を読み込む
+    fn load_with(extra: &str) -> std::result::Result<AppConfig, config::ConfigError> {
+        let raw_toml = format!(
+            r#"
+            discord_token = "token"
+            samurai_data_path = "/path/samurai.csv"
+            default_ollama_base_url = "http://127.0.0.1:11434"
+            default_ollama_model = "llama3.2:1b"
+            default_system_prompt_path = "/path/system_prompt.txt"
+            {}
+            "#,
+            extra
+        );
+        Config::builder()
+            .add_source(File::from_str(&raw_toml, config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()
+    }
+
+    #[test]
+    fn rejects_invalid_daily_time() {
+        for time in ["9", "24:00", "12:60", "ab:cd"] {
+            let error = load_with(&format!("[daily]\ntime = \"{}\"", time))
+                .unwrap_err()
+                .to_string();
+            assert!(
+                error.contains(&format!(
+                    "Invalid daily post time (expected HH:MM): {}",
+                    time
+                )),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_trigger_patterns() {
+        let error = load_with("[triggers.zaurus]\npatterns = [\"ザ+ウルス\", \"侍(\"]")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid trigger pattern '侍('"), "{}", error);
+
+        let config = load_with("[triggers.zaurus]\npatterns = [\"ザ+ウルス\"]").unwrap();
+        assert_eq!(config.triggers.zaurus.patterns, vec!["ザ+ウルス"]);
+    }
+
+    #[test]
+    fn load_trigger_cooldowns() {
+        let raw_toml = r#"
+        discord_token = "token"
+        samurai_data_path = "/path/samurai.csv"
+        default_ollama_base_url = "http://127.0.0.1:11434"
+        default_ollama_model = "llama3.2:1b"
+        default_system_prompt_path = "/path/system_prompt.txt"
+        random_seed = 42
+
+        [dataset]
+        format = "yaml"
+        columns = { name = "名前", description = "説明" }
+
+        [triggers]
+        resolution = "all"
+        default_mode = "message_final"
+        markdown = { ignore_spoilers = false }
+
+        [triggers.zaurus]
+        mode = "word_final"
+        priority = 10
+        phrases = ["ティラノ"]
+        patterns = ["ザ+ウルス"]
+
+        [triggers.samurai]
+        emojis = ["kasu"]
+        stickers = ["侍スタンプ"]
+        reactions = ["kasu", "⚔️"]
+
+        [triggers.samurai.cooldown]
+        react_while_cooling = true
+        user = { capacity = 3, refill_secs = 60 }
+
+        [triggers.zaurus.cooldown]
+        guild = { capacity = 5, refill_secs = 12.5 }
+
+        [triggers.zaurus.access]
+        allow_roles = [234567890123456789]
+        deny_channels = [1, 2]
+
+        [triggers.zaurus.normalize]
+        nfkc = true
+        strip_whitespace = true
+
+        [edits]
+        on_trigger_removed = "delete"
+
+        [samurai_reply]
+        style = "text"
+        color = 0x123456
+
+        [samurai_reply.rarity_colors]
+        SSR = 0xFFD700
+
+        [templates]
+        samurai = "{author}さん、今日の侍は {name} です！"
+
+        [buttons]
+        expiry_secs = 60
+        reroll = { capacity = 1, refill_secs = 30 }
+
+        [daily]
+        channels = [345678901234567890]
+        time = "07:30"
+
+        [templates.guilds."42"]
+        zaurus = "🦖 {reply}"
+        "#;
+
+        let config = Config::builder()
+            .add_source(File::from_str(raw_toml, config::FileFormat::Toml))
+            .build()
+            .expect("build config");
+        let app_config: AppConfig = config.try_deserialize().expect("deserialize AppConfig");
+
+        let samurai = &app_config.triggers.samurai.cooldown;
+        assert!(samurai.react_while_cooling);
+        assert_eq!(
+            samurai.user,
+            Some(BucketConfig {
+                capacity: 3,
+                refill_secs: 60.0
+            })
+        );
+        assert!(samurai.channel.is_none());
+
+        let zaurus = &app_config.triggers.zaurus.cooldown;
+        assert!(!zaurus.react_while_cooling);
+        assert_eq!(
+            zaurus.guild,
+            Some(BucketConfig {
+                capacity: 5,
+                refill_secs: 12.5
+            })
+        );
+
+        assert_eq!(
+            app_config.triggers.mode(Trigger::Samurai),
+            MatchMode::MessageFinal
+        );
+        assert_eq!(
+            app_config.triggers.mode(Trigger::Zaurus),
+            MatchMode::WordFinal
+        );
+        assert!(!app_config.triggers.markdown.ignore_spoilers);
+        assert_eq!(app_config.triggers.resolution, ResolutionPolicy::All);
+        assert_eq!(app_config.random_seed, Some(42));
+        assert_eq!(app_config.dataset.format, Some(DatasetFormat::Yaml));
+        assert_eq!(app_config.dataset.columns.name, "名前");
+        assert_eq!(app_config.dataset.columns.description, "説明");
+        assert_eq!(app_config.dataset.columns.rarity, "Rarity");
+        assert_eq!(app_config.triggers.zaurus.priority, 10);
+        assert_eq!(app_config.triggers.zaurus.phrases, vec!["ティラノ"]);
+        assert_eq!(app_config.triggers.zaurus.patterns, vec!["ザ+ウルス"]);
+        assert!(app_config.triggers.samurai.patterns.is_empty());
+        assert_eq!(app_config.triggers.samurai.emojis, vec!["kasu"]);
+        assert_eq!(app_config.triggers.samurai.stickers, vec!["侍スタンプ"]);
+        assert_eq!(app_config.triggers.samurai.reactions, vec!["kasu", "⚔️"]);
+
+        let normalize = &app_config.triggers.get(Trigger::Zaurus).normalize;
+        assert!(normalize.nfkc && !normalize.kana && normalize.strip_whitespace);
+        assert_eq!(
+            app_config.triggers.get(Trigger::Samurai).normalize,
+            NormalizeConfig::default()
+        );
+
+        let access = &app_config.triggers.get(Trigger::Zaurus).access;
+        assert_eq!(access.allow_roles, vec![234567890123456789]);
+        assert_eq!(access.deny_channels, vec![1, 2]);
+        assert!(
+            app_config
+                .triggers
+                .get(Trigger::Samurai)
+                .access
+                .allow_channels
+                .is_empty()
+        );
+
+        assert_eq!(
+            app_config.edits.on_trigger_removed,
+            TriggerRemovedAction::Delete
+        );
+        let reply = &app_config.samurai_reply;
+        assert_eq!(reply.style, ReplyStyle::Text);
+        assert_eq!(reply.color_for(Some("SSR")), 0xFFD700);
+        assert_eq!(reply.color_for(Some("SR")), 0x123456);
+        assert_eq!(reply.color_for(None), 0x123456);
+
+        assert_eq!(app_config.daily.channels, vec![345678901234567890]);
+        assert_eq!(app_config.daily.time.secs, 7 * 60 * 60 + 30 * 60);
+
+        let templates = &app_config.templates;
+        assert_eq!(
+            templates.get(Trigger::Samurai, Some(42)),
+            "{author}さん、今日の侍は {name} です！"
+        );
+        assert_eq!(templates.get(Trigger::Zaurus, Some(42)), "🦖 {reply}");
+        assert_eq!(
+            templates.get(Trigger::Zaurus, Some(1)),
+            TemplatesConfig::DEFAULT_ZAURUS
+        );
+        assert_eq!(
+            templates.get(Trigger::Zaurus, None),
+            TemplatesConfig::DEFAULT_ZAURUS
+        );
+
+        let buttons = &app_config.buttons;
+        assert!(buttons.enabled);
+        assert_eq!(buttons.expiry_secs, 60);
+        assert_eq!(buttons.reroll.capacity, 1);
+    }
+
+    #[test]
+    fn load_trigger_gates() {
+        let app_config = load_with(
+            r#"
+            [triggers.samurai.gate]
+            probability = 0.25
+            active_hours = "09:00-23:30"
+            days = ["sat", "日"]
+            quiet_hours = ["23:00-06:00"]
+            "#,
+        )
+        .expect("deserialize AppConfig");
+
+        let gate = &app_config.triggers.samurai.gate;
+        assert_eq!(gate.probability, 0.25);
+        assert_eq!(
+            gate.active_hours,
+            Some(TimeWindow {
+                start: 9 * 60 * 60,
+                end: 23 * 60 * 60 + 30 * 60
+            })
+        );
+        assert_eq!(gate.days, vec![Weekday::Sat, Weekday::Sun]);
+        assert_eq!(
+            gate.quiet_hours,
+            vec![TimeWindow {
+                start: 23 * 60 * 60,
+                end: 6 * 60 * 60
+            }]
+        );
+        assert_eq!(app_config.triggers.zaurus.gate, GateConfig::default());
+        assert_eq!(app_config.triggers.zaurus.gate.probability, 1.0);
+
+        // 範囲外の確率・形式の正しくない時間帯は読み込み時にエラーにする
+        let cases = [
+            ("probability = 1.5", "Invalid gate probability"),
+            ("probability = -0.1", "Invalid gate probability"),
+            (
+                "active_hours = \"09:00-24:00\"",
+                "Invalid gate time window (expected HH:MM-HH:MM): 09:00-24:00",
+            ),
+            (
+                "quiet_hours = [\"9-18\"]",
+                "Invalid gate time window (expected HH:MM-HH:MM): 9-18",
+            ),
+        ];
+        for (gate, expected) in cases {
+            let error = load_with(&format!("[triggers.samurai.gate]\n{}", gate))
+                .unwrap_err()
+                .to_string();
+            assert!(error.contains(expected), "{}: {}", gate, error);
+        }
     }
 }
@@ -0,0 +1,302 @@
+use crate::config::{BucketConfig, CooldownConfig};
+use crate::detect::Trigger;
+use crate::platform::IncomingMessage;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// 保持するバケット数の上限（超えたら満タンのバケットを掃除する）
+const MAX_BUCKETS: usize = 10_000;
+
+/// クールダウンを判定する単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Scope {
+    User,
+    Channel,
+    Guild,
+}
+
+/// バケットを識別するキー（トリガー・単位・ID）
+type BucketKey = (Trigger, Scope, u64);
+
+/// トークンバケット
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    /// 経過時間に応じてトークンを回復させる
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        let rate = if config.refill_secs > 0.0 {
+            elapsed / config.refill_secs
+        } else {
+            f64::INFINITY
+        };
+        self.tokens = (self.tokens + rate).min(f64::from(config.capacity));
+        self.updated_at = now;
+    }
+
+    /// バケットが満タンかどうか（掃除してよいかどうか）
+    fn is_full(&self, config: &BucketConfig, now: Instant) -> bool {
+        let mut bucket = *self;
+        bucket.refill(config, now);
+        bucket.tokens >= f64::from(config.capacity)
+    }
+}
+
+/// クールダウン判定の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CooldownDecision {
+    /// 通常どおり応答してよい
+    Allowed,
+    /// クールダウン中（`react_only` が真ならリアクションだけは行う）
+    CoolingDown { react_only: bool },
+}
+
+/// トリガーごと・ユーザー/チャンネル/サーバーごとのクールダウンを管理する構造体
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    buckets: Mutex<HashMap<BucketKey, TokenBucket>>,
+}
+
+impl Cooldowns {
+    /// 新しい Cooldowns を作成する関数
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 現在時刻でクールダウンを判定する関数
+    /// # 引数
+    /// * `trigger` - 検知したトリガー
+    /// * `config` - トリガーのクールダウン設定
+    /// * `msg` - 受信したメッセージ
+    /// # 戻り値
+    /// * `CooldownDecision` - 判定結果（許可された場合はトークンを消費済み）
+    pub fn check(
+        &self,
+        trigger: Trigger,
+        config: &CooldownConfig,
+        msg: &IncomingMessage,
+    ) -> CooldownDecision {
+        self.check_at(trigger, config, msg, Instant::now())
+    }
+
+    /// 指定時刻でクールダウンを判定する関数
+    ///
+    /// 設定されたすべてのバケットにトークンが残っている場合だけ、各バケットから1つずつ消費します。
+    /// # 引数
+    /// * `trigger` - 検知したトリガー
+    /// * `config` - トリガーのクールダウン設定
+    /// * `msg` - 受信したメッセージ
+    /// * `now` - 判定に使う現在時刻
+    /// # 戻り値
+    /// * `CooldownDecision` - 判定結果
+    pub fn check_at(
+        &self,
+        trigger: Trigger,
+        config: &CooldownConfig,
+        msg: &IncomingMessage,
+        now: Instant,
+    ) -> CooldownDecision {
+        let scopes: Vec<(BucketKey, BucketConfig)> = [
+            (Scope::User, Some(msg.author_id), config.user),
+            (Scope::Channel, Some(msg.channel_id), config.channel),
+            (Scope::Guild, msg.guild_id, config.guild),
+        ]
+        .into_iter()
+        .filter_map(|(scope, id, bucket)| Some(((trigger, scope, id?), bucket?)))
+        .collect();
+        if scopes.is_empty() {
+            return CooldownDecision::Allowed;
+        }
+
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return CooldownDecision::Allowed;
+        };
+        if buckets.len() > MAX_BUCKETS {
+            Self::prune(&mut buckets, trigger, config, now);
+        }
+
+        // 先にすべてのバケットを回復させ、1つでも空なら消費せずにクールダウン扱いにする
+        let mut allowed = true;
+        for (key, bucket_config) in &scopes {
+            let bucket = buckets.entry(*key).or_insert(TokenBucket {
+                tokens: f64::from(bucket_config.capacity),
+                updated_at: now,
+            });
+            bucket.refill(bucket_config, now);
+            if bucket.tokens < 1.0 {
+                allowed = false;
+            }
+        }
+        if !allowed {
+            return CooldownDecision::CoolingDown {
+                react_only: config.react_while_cooling,
+            };
+        }
+
+        for (key, _) in &scopes {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        CooldownDecision::Allowed
+    }
+
+    /// 指定トリガーのうち満タンに戻っているバケットを削除する（削除しても挙動は変わらない）
+    fn prune(
+        buckets: &mut HashMap<BucketKey, TokenBucket>,
+        trigger: Trigger,
+        config: &CooldownConfig,
+        now: Instant,
+    ) {
+        buckets.retain(|(bucket_trigger, scope, _), bucket| {
+            if *bucket_trigger != trigger {
+                return true;
+            }
+            let bucket_config = match scope {
+                Scope::User => config.user,
+                Scope::Channel => config.channel,
+                Scope::Guild => config.guild,
+            };
+            bucket_config.is_some_and(|c| !bucket.is_full(&c, now))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn message(author_id: u64, channel_id: u64) -> IncomingMessage {
+        IncomingMessage {
+            id: 1,
+            channel_id,
+            guild_id: Some(100),
+            author_id,
+            content: "侍".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn bucket(capacity: u32, refill_secs: f64) -> Option<BucketConfig> {
+        Some(BucketConfig {
+            capacity,
+            refill_secs,
+        })
+    }
+
+    #[test]
+    fn allows_everything_without_buckets() {
+        let cooldowns = Cooldowns::new();
+        let config = CooldownConfig::default();
+        for _ in 0..100 {
+            assert_eq!(
+                cooldowns.check(Trigger::Samurai, &config, &message(1, 1)),
+                CooldownDecision::Allowed
+            );
+        }
+    }
+
+    #[test]
+    fn user_bucket_limits_and_refills() {
+        let cooldowns = Cooldowns::new();
+        let config = CooldownConfig {
+            user: bucket(2, 10.0),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let msg = message(1, 1);
+
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &msg, start),
+            CooldownDecision::Allowed
+        );
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &msg, start),
+            CooldownDecision::Allowed
+        );
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &msg, start),
+            CooldownDecision::CoolingDown { react_only: false }
+        );
+
+        // 別ユーザー・別トリガーは独立している
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &message(2, 1), start),
+            CooldownDecision::Allowed
+        );
+        assert_eq!(
+            cooldowns.check_at(Trigger::Zaurus, &config, &msg, start),
+            CooldownDecision::Allowed
+        );
+
+        // 10 秒で1回分回復する
+        let later = start + Duration::from_secs(10);
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &msg, later),
+            CooldownDecision::Allowed
+        );
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &msg, later),
+            CooldownDecision::CoolingDown { react_only: false }
+        );
+    }
+
+    #[test]
+    fn channel_bucket_is_shared_between_users() {
+        let cooldowns = Cooldowns::new();
+        let config = CooldownConfig {
+            channel: bucket(1, 60.0),
+            react_while_cooling: true,
+            ..Default::default()
+        };
+        let now = Instant::now();
+
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &message(1, 5), now),
+            CooldownDecision::Allowed
+        );
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &message(2, 5), now),
+            CooldownDecision::CoolingDown { react_only: true }
+        );
+        assert_eq!(
+            cooldowns.check_at(Trigger::Samurai, &config, &message(2, 6), now),
+            CooldownDecision::Allowed
+        );
+    }
+
+    #[test]
+    fn rejected_check_does_not_consume_other_buckets() {
+        let cooldowns = Cooldowns::new();
+        let config = CooldownConfig {
+            user: bucket(5, 60.0),
+            guild: bucket(1, 60.0),
+            ..Default::default()
+        };
+        let now = Instant::now();
+
+        assert_eq!(
+            cooldowns.check_at(Trigger::Zaurus, &config, &message(1, 1), now),
+            CooldownDecision::Allowed
+        );
+        for _ in 0..10 {
+            assert!(matches!(
+                cooldowns.check_at(Trigger::Zaurus, &config, &message(1, 1), now),
+                CooldownDecision::CoolingDown { .. }
+            ));
+        }
+
+        // サーバーのバケットが回復すれば、ユーザーのバケットはまだ残っている
+        let later = now + Duration::from_secs(60);
+        assert_eq!(
+            cooldowns.check_at(Trigger::Zaurus, &config, &message(1, 1), later),
+            CooldownDecision::Allowed
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::handler::{self, HandlerDeps};
+use crate::platform::{ChatPlatform, Embed};
+use crate::stats::{self, JST_OFFSET_SECS, SECS_PER_DAY};
+use crate::table::SamuraiEntry;
+use anyhow::{Context, Result, bail};
+use log::{error, info};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 時刻（`"HH:MM"` 形式）を、日本時間の0時からの秒数に変換する関数
+/// # 引数
+/// * `time` - 時刻（例: `"09:00"`）
+/// # 戻り値
+/// * `Ok(u64)` - 0時からの秒数
+/// * `Err(anyhow::Error)` - 形式が正しくない場合
+pub fn parse_time(time: &str) -> Result<u64> {
+    let invalid = || format!("Invalid time (expected HH:MM): {}", time);
+    let (hour, minute) = time.split_once(':').with_context(invalid)?;
+    let hour: u64 = hour.trim().parse().with_context(invalid)?;
+    let minute: u64 = minute.trim().parse().with_context(invalid)?;
+    if hour >= 24 || minute >= 60 {
+        bail!(invalid());
+    }
+    Ok(hour * 60 * 60 + minute * 60)
+}
+
+/// 日付から「今日の侍」の位置を決める関数（同じ日付なら常に同じ位置を返す）
+/// # 引数
+/// * `day` - 日本時間の日付の通し番号
+/// * `len` - 侍データの件数
+/// # 戻り値
+/// * `None` - 侍データが空の場合
+pub fn pick_index(day: u32, len: usize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    // SplitMix64 で、連続する日付でも偏りなく散らす（実行環境によらず同じ結果になる）
+    let mut z = u64::from(day).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    Some((z % len as u64) as usize)
+}
+
+/// 指定した時刻（日本時間の日付）の「今日の侍」を返す関数
+/// # 引数
+/// * `entries` - 侍データのテーブル
+/// * `now` - UNIX 時刻（秒）
+pub fn today_entry(entries: &[SamuraiEntry], now: u64) -> Option<&SamuraiEntry> {
+    entries.get(pick_index(stats::jst_day(now), entries.len())?)
+}
+
+/// 「今日の侍」の埋め込みを作成する関数
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `entry` - 今日の侍
+pub fn daily_embed(deps: &HandlerDeps, entry: &SamuraiEntry) -> Embed {
+    let mut embed = handler::samurai_embed(&deps.samurai_reply, entry, false);
+    embed.title = format!("今日の侍: {}", entry.name);
+    embed
+}
+
+/// 次の投稿時刻までの秒数を返す関数（ちょうど投稿時刻の場合は翌日まで）
+/// # 引数
+/// * `now` - 現在の UNIX 時刻（秒）
+/// * `at` - 投稿時刻（日本時間の0時からの秒数）
+pub fn secs_until(now: u64, at: u64) -> u64 {
+    let elapsed = (now + JST_OFFSET_SECS) % SECS_PER_DAY;
+    if at > elapsed {
+        at - elapsed
+    } else {
+        SECS_PER_DAY - elapsed + at
+    }
+}
+
+/// 設定されたすべてのチャンネルに「今日の侍」を投稿する関数
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `platform` - 投稿に使うプラットフォーム
+/// * `now` - 現在の UNIX 時刻（秒）
+pub async fn post(deps: &HandlerDeps, platform: &dyn ChatPlatform, now: u64) {
+    let Some(entry) = today_entry(&deps.samurai_entries, now) else {
+        error!("Samurai not found");
+        return;
+    };
+    let embed = daily_embed(deps, entry);
+    for channel_id in &deps.daily.channels {
+        match platform.send_embed(*channel_id, &embed).await {
+            Ok(_) => info!(
+                "Posted today's samurai to channel {}: {}",
+                channel_id, entry.name
+            ),
+            Err(e) => error!("{:?}", e),
+        }
+    }
+}
+
+/// 毎日決まった時刻に「今日の侍」を投稿し続ける関数
+///
+/// 投稿先が設定されていない場合は何もせずに終了します（投稿時刻は設定の読み込み時に検証済み）。
+/// シャットダウンが始まると、次の投稿時刻に終了します。
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `platform` - 投稿に使うプラットフォーム
+pub async fn run(deps: Arc<HandlerDeps>, platform: impl ChatPlatform) {
+    if deps.daily.channels.is_empty() {
+        return;
+    }
+    let at = deps.daily.time.secs;
+
+    let mut last_posted = None;
+    loop {
+        let wait = secs_until(deps.clock.now(), at);
+        info!("Next daily samurai post in {} seconds", wait);
+        tokio::time::sleep(Duration::from_secs(wait)).await;
+
+        let Some(_in_flight) = deps.shutdown.try_begin() else {
+            info!("Shutting down; stopping daily samurai posts");
+            return;
+        };
+        // 時計のずれで早く起きた場合も、同じ日に二重投稿しない
+        let now = deps.clock.now();
+        let today = stats::jst_day(now);
+        if last_posted == Some(today) {
+            continue;
+        }
+        post(&deps, &platform, now).await;
+        last_posted = Some(today);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::{FixedChat, deps};
+    use crate::platform::{RecordedAction, RecordingPlatform};
+
+    /// 2024-01-03 12:00 JST
+    const NOON: u64 = 1_704_250_800;
+
+    #[test]
+    fn parses_post_times() {
+        assert_eq!(parse_time("09:00").unwrap(), 9 * 60 * 60);
+        assert_eq!(parse_time("23:59").unwrap(), 23 * 60 * 60 + 59 * 60);
+        for time in ["", "9", "24:00", "12:60", "ab:cd"] {
+            assert!(parse_time(time).is_err(), "{}", time);
+        }
+    }
+
+    #[test]
+    fn waits_until_next_post_time() {
+        let hour = 60 * 60;
+        assert_eq!(secs_until(NOON, 13 * hour), hour);
+        assert_eq!(secs_until(NOON, 12 * hour), SECS_PER_DAY);
+        assert_eq!(secs_until(NOON, 9 * hour), 21 * hour);
+    }
+
+    #[test]
+    fn picks_same_entry_for_the_whole_day() {
+        let entries: Vec<SamuraiEntry> = (0..50)
+            .map(|id| SamuraiEntry {
+                id,
+                ..Default::default()
+            })
+            .collect();
+        // 日本時間の 0:00 から 23:59 までは同じ侍
+        let midnight = NOON - 12 * 60 * 60;
+        let today = today_entry(&entries, midnight).unwrap();
+        assert_eq!(today_entry(&entries, NOON), Some(today));
+        assert_eq!(
+            today_entry(&entries, midnight + SECS_PER_DAY - 1),
+            Some(today)
+        );
+
+        // 日付が変われば別の侍も選ばれる
+        let picks: std::collections::HashSet<usize> =
+            (0..30).filter_map(|day| pick_index(day, 50)).collect();
+        assert!(picks.len() > 10);
+        assert_eq!(pick_index(0, 0), None);
+    }
+
+    #[tokio::test]
+    async fn posts_to_every_configured_channel() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(None));
+        deps.daily.channels = vec![1, 2];
+
+        post(&deps, &platform, NOON).await;
+        let actions = platform.take_actions();
+        assert_eq!(actions.len(), 2);
+        for (action, expected) in actions.iter().zip([1, 2]) {
+            match action {
+                RecordedAction::SendEmbed { channel_id, embed } => {
+                    assert_eq!(*channel_id, expected);
+                    assert_eq!(embed.title, "今日の侍: テスト侍");
+                }
+                other => panic!("unexpected action: {:?}", other),
+            }
+        }
+    }
+}
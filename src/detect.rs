@@ -1,38 +1,592 @@
+use crate::config::{MarkdownConfig, MatchMode, NormalizeConfig, TriggerConfig, TriggersConfig};
+use crate::platform::Emoji;
+use aho_corasick::AhoCorasick;
+use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
-use regex::Regex;
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
 
-// 正規表現オブジェクトを初回起動時にコンパイルして静的に保持
-static RE_SAMURAI_PHRASE: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"[\s\S]*?侍").expect("Failed to compile samurai regex"));
-static RE_ZAURUS_PHRASE: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"[\s\S]*?ザウルス").expect("Failed to compile zaurus regex"));
+// Discord の Markdown 記法を取り除く正規表現を初回使用時にコンパイルして静的に保持
+static RE_CODE_BLOCK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)```.*?```").expect("Failed to compile code block regex"));
+static RE_INLINE_CODE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"``[^\n]+?``|`[^`\n]+`").expect("Failed to compile inline code regex")
+});
+static RE_MULTILINE_QUOTE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?ms)^>>> .*").expect("Failed to compile quote regex"));
+static RE_QUOTE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^> .*$").expect("Failed to compile quote regex"));
+static RE_SPOILER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)\|\|(.*?)\|\|").expect("Failed to compile spoiler regex"));
+static RE_URL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<?https?://[^\s>]+>?").expect("Failed to compile URL regex"));
+static RE_CUSTOM_EMOJI: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<a?:(\w+):\d+>").expect("Failed to compile custom emoji regex"));
+static RE_MENTION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<(?:@[!&]?|#)\d+>").expect("Failed to compile mention regex"));
 
-/// 文章内に「〇〇侍」という形式のフレーズ（改行を含む可能性あり）が含まれているかどうかを判定する関数
-/// # 引数
-/// * `text` - 判定対象の文章 (文字列スライス)
+/// 検知対象のトリガー
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// 「侍」を含むメッセージ
+    Samurai,
+    /// 「ザウルス」を含むメッセージ
+    Zaurus,
+}
+
+impl Trigger {
+    /// すべてのトリガー（優先度が同じ場合はこの順に優先する）
+    pub const ALL: [Trigger; 2] = [Trigger::Samurai, Trigger::Zaurus];
+
+    /// 組み込みの検知する語句（正規化の設定によって表記ゆれも検知する）
+    ///
+    /// 侍の読み「サムライ」は、カナの表記ゆれ（`kana`）を有効にした場合のみ検知します。
+    /// # 引数
+    /// * `normalize` - トリガーの正規化の設定
+    pub fn phrases(self, normalize: &NormalizeConfig) -> &'static [&'static str] {
+        match self {
+            Trigger::Samurai if normalize.kana => &["侍", "サムライ"],
+            Trigger::Samurai => &["侍"],
+            Trigger::Zaurus => &["ザウルス"],
+        }
+    }
+
+    /// 設定ファイルやメトリクスで使うトリガー名を返す関数
+    /// # 戻り値
+    /// * `&'static str` - トリガー名（例: `"samurai"`）
+    pub fn name(self) -> &'static str {
+        match self {
+            Trigger::Samurai => "samurai",
+            Trigger::Zaurus => "zaurus",
+        }
+    }
+}
+
+/// 見えない文字として取り除くゼロ幅文字
+const ZERO_WIDTH_CHARS: [char; 5] = ['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// 検知したトリガーの語句と、その位置
+/// # フィールド
+/// * `trigger` - 検知したトリガー
+/// * `start` / `end` - 語句の位置（検知に渡した文字列のバイト位置、`end` は含まない）
+///   （カスタム絵文字はタグ全体の位置、スタンプは本文の末尾の位置）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerMatch {
+    pub trigger: Trigger,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// トリガーの語句・正規表現をまとめてコンパイルした検知器
 ///
-/// # 戻り値
-/// * `true` - フレーズが1つ以上含まれている場合
-/// * `false` - フレーズが含まれていない場合
-pub fn contains_samurai_phrase(text: &str) -> bool {
-    RE_SAMURAI_PHRASE.is_match(text)
+/// 正規化の設定が同じトリガーをひとまとめにし、語句は Aho-Corasick、正規表現は RegexSet で
+/// 検知するため、トリガーや語句が増えてもメッセージの走査は正規化の設定ごとに1回で済みます。
+/// # フィールド
+/// * `groups` - 正規化の設定ごとの検知器
+/// * `modes` - トリガーごとの検知モード
+/// * `markdown` - 検知の前に取り除く Markdown 記法の設定
+/// * `emojis` / `stickers` / `reactions` - カスタム絵文字・スタンプ・リアクションの名前とトリガー
+#[derive(Debug, Clone)]
+pub struct TriggerMatcher {
+    groups: Vec<MatcherGroup>,
+    modes: HashMap<Trigger, MatchMode>,
+    markdown: MarkdownConfig,
+    emojis: Vec<(String, Trigger)>,
+    stickers: Vec<(String, Trigger)>,
+    reactions: Vec<(String, Trigger)>,
+}
+
+impl TriggerMatcher {
+    /// トリガーの設定から検知器を作成する関数
+    /// # 引数
+    /// * `config` - トリガーごとの動作設定
+    /// # 戻り値
+    /// * `Ok(TriggerMatcher)` - コンパイルに成功した場合
+    /// * `Err(anyhow::Error)` - 正規表現が正しくない場合
+    pub fn new(config: &TriggersConfig) -> Result<Self> {
+        // 正規化の設定ごとに、正規化済みの語句と正規表現を集める
+        let mut specs: Vec<GroupSpec> = Vec::new();
+        for trigger in Trigger::ALL {
+            let trigger_config = config.get(trigger);
+            let normalize = trigger_config.normalize;
+            let spec = match specs.iter().position(|spec| spec.normalize == normalize) {
+                Some(i) => &mut specs[i],
+                None => {
+                    specs.push(GroupSpec {
+                        normalize,
+                        literals: Vec::new(),
+                        patterns: Vec::new(),
+                    });
+                    specs.last_mut().expect("just pushed")
+                }
+            };
+            let phrases = trigger
+                .phrases(&normalize)
+                .iter()
+                .copied()
+                .chain(trigger_config.phrases.iter().map(String::as_str));
+            for phrase in phrases {
+                let literal = (normalize_text(phrase, &normalize), trigger);
+                // 正規化で同じになる語句（「さむらい」と「サムライ」など）は1つにまとめる
+                if !literal.0.is_empty() && !spec.literals.contains(&literal) {
+                    spec.literals.push(literal);
+                }
+            }
+            spec.patterns.extend(
+                trigger_config
+                    .patterns
+                    .iter()
+                    .map(|pattern| (pattern.clone(), trigger)),
+            );
+        }
+
+        // 絵文字・スタンプ・リアクションは名前の完全一致で判定する（`:kasu:` の形式でもよい）
+        let names = |select: fn(&TriggerConfig) -> &Vec<String>| -> Vec<(String, Trigger)> {
+            Trigger::ALL
+                .into_iter()
+                .flat_map(|trigger| {
+                    select(config.get(trigger))
+                        .iter()
+                        .map(move |name| (name.trim_matches(':').to_string(), trigger))
+                })
+                .collect()
+        };
+
+        Ok(Self {
+            emojis: names(|c| &c.emojis),
+            stickers: names(|c| &c.stickers),
+            reactions: names(|c| &c.reactions),
+            groups: specs
+                .into_iter()
+                .map(MatcherGroup::new)
+                .collect::<Result<_>>()?,
+            modes: Trigger::ALL
+                .into_iter()
+                .map(|trigger| (trigger, config.mode(trigger)))
+                .collect(),
+            markdown: config.markdown.clone(),
+        })
+    }
+
+    /// メッセージに含まれるすべてのトリガーの語句・絵文字・スタンプを、出現順に返す関数
+    ///
+    /// Markdown 記法を取り除いたあと、トリガーごとの正規化・検知モードで判定します。
+    /// 位置は Markdown 記法を取り除く前の本文でのバイト位置です。
+    /// カスタム絵文字・スタンプには検知モードを適用しません。
+    /// # 引数
+    /// * `text` - メッセージの本文
+    /// * `stickers` - メッセージに付いているスタンプの名前
+    /// # 戻り値
+    /// * `Vec<TriggerMatch>` - 一致した語句（出現位置の順、同じ位置ならトリガーの順）
+    pub fn find(&self, text: &str, stickers: &[String]) -> Vec<TriggerMatch> {
+        // コード・引用・URL などの中の語句・絵文字には反応しない
+        let stripped = Stripped::new(text, &self.markdown);
+        let mut matches = Vec::new();
+        for (pos, name) in &stripped.emojis {
+            let (start, end) = stripped.origin(*pos, pos + 1);
+            matches.extend(find_names(&self.emojis, name).map(|trigger| TriggerMatch {
+                trigger,
+                start,
+                end,
+            }));
+        }
+        // スタンプは本文のあとに表示されるので、本文の末尾にあるものとして扱う
+        for name in stickers {
+            matches.extend(
+                find_names(&self.stickers, name).map(|trigger| TriggerMatch {
+                    trigger,
+                    start: text.len(),
+                    end: text.len(),
+                }),
+            );
+        }
+        for group in &self.groups {
+            let normalized = Normalized::new(&stripped.text, &group.normalize);
+            for (trigger, start, end) in group.find(&normalized.text) {
+                let mode = self.modes.get(&trigger).copied().unwrap_or_default();
+                if start == end || !satisfies_mode(&normalized.text[end..], mode) {
+                    continue;
+                }
+                // 正規化前 → 記法を取り除く前 の順に位置を戻す
+                let (start, end) =
+                    stripped.origin(normalized.origins[start].0, normalized.origins[end - 1].1);
+                matches.push(TriggerMatch {
+                    trigger,
+                    start,
+                    end,
+                });
+            }
+        }
+        matches.sort_by_key(|m| {
+            let order = Trigger::ALL.iter().position(|t| *t == m.trigger);
+            (m.start, order, m.end)
+        });
+        // 語句と正規表現の両方に一致した場合などの重複を取り除く
+        matches.dedup();
+        matches
+    }
+
+    /// メッセージに付けられたリアクションに対応するトリガーを返す関数
+    /// # 引数
+    /// * `emoji` - 付けられたリアクション
+    /// # 戻り値
+    /// * `Vec<Trigger>` - 対応するトリガー（トリガーの順）
+    pub fn find_reaction(&self, emoji: &Emoji) -> Vec<Trigger> {
+        let name = match emoji {
+            Emoji::Custom(name) | Emoji::Unicode(name) => name,
+        };
+        find_names(&self.reactions, name).collect()
+    }
 }
 
-/// 文章内に「〇〇ザウルス」という形式のフレーズ（改行を含む可能性あり）が含まれているかどうかを判定する関数
+/// 名前とトリガーの組から、名前が一致するトリガーを返す
+fn find_names<'a>(
+    names: &'a [(String, Trigger)],
+    name: &'a str,
+) -> impl Iterator<Item = Trigger> + 'a {
+    names
+        .iter()
+        .filter(move |(n, _)| n == name)
+        .map(|(_, trigger)| *trigger)
+}
+
+/// 検知器をコンパイルする前の、正規化の設定ごとの語句と正規表現
+struct GroupSpec {
+    normalize: NormalizeConfig,
+    literals: Vec<(String, Trigger)>,
+    patterns: Vec<(String, Trigger)>,
+}
+
+/// 同じ正規化の設定を使うトリガーの検知器
+/// # フィールド
+/// * `normalize` - 正規化の設定
+/// * `literals` - 正規化済みの語句をまとめて検知する Aho-Corasick
+/// * `literal_triggers` - 語句の番号ごとのトリガー
+/// * `patterns` - どの正規表現が一致するかをまとめて判定する RegexSet
+/// * `regexes` - 一致した位置を求めるための個々の正規表現（`patterns` と同じ順）
+/// * `pattern_triggers` - 正規表現の番号ごとのトリガー
+#[derive(Debug, Clone)]
+struct MatcherGroup {
+    normalize: NormalizeConfig,
+    literals: AhoCorasick,
+    literal_triggers: Vec<Trigger>,
+    patterns: RegexSet,
+    regexes: Vec<Regex>,
+    pattern_triggers: Vec<Trigger>,
+}
+
+impl MatcherGroup {
+    /// 語句と正規表現をコンパイルする
+    fn new(spec: GroupSpec) -> Result<Self> {
+        let (phrases, literal_triggers): (Vec<String>, Vec<Trigger>) =
+            spec.literals.into_iter().unzip();
+        let literals = AhoCorasick::new(&phrases).context("Failed to build trigger phrases")?;
+
+        let regexes = spec
+            .patterns
+            .iter()
+            .map(|(pattern, trigger)| {
+                Regex::new(pattern).with_context(|| {
+                    format!(
+                        "Invalid pattern for trigger {}: {}",
+                        trigger.name(),
+                        pattern
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let patterns = RegexSet::new(regexes.iter().map(Regex::as_str))
+            .context("Failed to build trigger patterns")?;
+
+        Ok(Self {
+            normalize: spec.normalize,
+            literals,
+            literal_triggers,
+            patterns,
+            regexes,
+            pattern_triggers: spec.patterns.into_iter().map(|(_, t)| t).collect(),
+        })
+    }
+
+    /// 正規化済みの文章から、語句・正規表現が一致した範囲をすべて返す
+    ///
+    /// 語句は重なりも含めて1回の走査で探し、正規表現は RegexSet で一致したものだけ位置を求めます。
+    fn find<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (Trigger, usize, usize)> + 'a {
+        let literals = self.literals.find_overlapping_iter(text).map(|m| {
+            (
+                self.literal_triggers[m.pattern().as_usize()],
+                m.start(),
+                m.end(),
+            )
+        });
+        let patterns = self.patterns.matches(text).into_iter().flat_map(move |i| {
+            self.regexes[i]
+                .find_iter(text)
+                .map(move |m| (self.pattern_triggers[i], m.start(), m.end()))
+        });
+        literals.chain(patterns)
+    }
+}
+
+/// 語句の直後に続く文字列から、一致した位置がモードの条件を満たすかを判定する
+///
+/// 文字・数字（漢字・かなを含む）以外の記号・絵文字・空白は末尾とみなして無視します。
+fn satisfies_mode(rest: &str, mode: MatchMode) -> bool {
+    let has_word = |s: &str| s.chars().any(char::is_alphanumeric);
+    match mode {
+        MatchMode::Anywhere => true,
+        MatchMode::WordFinal => rest.chars().next().is_none_or(|c| !c.is_alphanumeric()),
+        MatchMode::MessageFinal => !has_word(rest),
+        MatchMode::LineFinal => !has_word(rest.split('\n').next().unwrap_or("")),
+    }
+}
+
+/// 検知の対象外にする Discord の Markdown 記法を取り除いた本文と、
+/// その各バイトが取り除く前のどの範囲から来たかの対応
+///
+/// コードブロック・インラインコード・引用・ネタバレ（設定による）・URL・カスタム絵文字・
+/// メンション（ユーザー・ロール・チャンネル）を空白に置き換えます。
+/// 前後の文字がつながって別の語句にならないよう、取り除いた部分には空白を残します。
+/// # フィールド
+/// * `text` - 記法を取り除いた本文
+/// * `origins` - 取り除いた後の各バイトに対応する、取り除く前の範囲（開始, 終了）
+///   （空白に置き換えた部分は、置き換えた記法全体の範囲）
+/// * `emojis` - 取り除いたカスタム絵文字の、置き換えた空白の位置と名前
+struct Stripped {
+    text: String,
+    origins: Vec<(usize, usize)>,
+    emojis: Vec<(usize, String)>,
+}
+
+impl Stripped {
+    /// 記法を順に取り除き、位置の対応を記録する
+    /// # 引数
+    /// * `text` - メッセージの本文
+    /// * `config` - 取り除く記法の設定
+    fn new(text: &str, config: &MarkdownConfig) -> Self {
+        let mut stripped = Self {
+            text: text.to_string(),
+            origins: (0..text.len()).map(|i| (i, i + 1)).collect(),
+            emojis: Vec::new(),
+        };
+        // コードの中の引用記号などを誤って解釈しないよう、コードを最初に取り除く
+        stripped.replace(&RE_CODE_BLOCK, None);
+        stripped.replace(&RE_INLINE_CODE, None);
+        stripped.replace(&RE_MULTILINE_QUOTE, None);
+        stripped.replace(&RE_QUOTE, None);
+        let keep_spoiler = (!config.ignore_spoilers).then_some(1);
+        stripped.replace(&RE_SPOILER, keep_spoiler);
+        stripped.replace(&RE_URL, None);
+        stripped.replace(&RE_MENTION, None);
+
+        // 位置がずれないよう、カスタム絵文字は最後に取り除く
+        stripped.emojis = stripped.replace(&RE_CUSTOM_EMOJI, None);
+        stripped
+    }
+
+    /// 正規表現に一致した部分を空白（`keep` を指定した場合はそのグループの中身）に置き換える
+    /// # 戻り値
+    /// * `Vec<(usize, String)>` - 空白に置き換えた位置と、1番目のグループの中身
+    fn replace(&mut self, re: &Regex, keep: Option<usize>) -> Vec<(usize, String)> {
+        let mut text = String::with_capacity(self.text.len());
+        let mut origins = Vec::with_capacity(self.origins.len());
+        let mut spaces = Vec::new();
+        let mut last = 0;
+        for caps in re.captures_iter(&self.text) {
+            let tag = caps.get(0).expect("group 0 always matches");
+            text.push_str(&self.text[last..tag.start()]);
+            origins.extend_from_slice(&self.origins[last..tag.start()]);
+            match keep.and_then(|i| caps.get(i)) {
+                Some(inner) => {
+                    text.push_str(inner.as_str());
+                    origins.extend_from_slice(&self.origins[inner.range()]);
+                }
+                None => {
+                    let group = caps.get(1).map_or("", |m| m.as_str());
+                    spaces.push((text.len(), group.to_string()));
+                    text.push(' ');
+                    origins.push(self.origin(tag.start(), tag.end()));
+                }
+            }
+            last = tag.end();
+        }
+        text.push_str(&self.text[last..]);
+        origins.extend_from_slice(&self.origins[last..]);
+        self.text = text;
+        self.origins = origins;
+        spaces
+    }
+
+    /// 取り除いた後の範囲を、取り除く前の範囲に変換する（空でない範囲のみ）
+    fn origin(&self, start: usize, end: usize) -> (usize, usize) {
+        (self.origins[start].0, self.origins[end - 1].1)
+    }
+}
+
+/// 検知の前に文字列を正規化する関数
+///
+/// ゼロ幅文字の除去 → NFKC（または幅の統一）→ ひらがなのカタカナ化 → 空白の除去 の順に適用します。
 /// # 引数
-/// * `text` - 判定対象の文章 (文字列スライス)
+/// * `text` - 対象の文字列
+/// * `config` - 正規化の設定
 /// # 戻り値
-/// * `true` - フレーズが1つ以上含まれている場合
-/// * `false` - フレーズが含まれていない場合
-pub fn contains_zaurus_phrase(text: &str) -> bool {
-    RE_ZAURUS_PHRASE.is_match(text)
+/// * `String` - 正規化後の文字列
+pub fn normalize_text(text: &str, config: &NormalizeConfig) -> String {
+    Normalized::new(text, config).text
+}
+
+/// 正規化した文字列と、その各バイトが正規化前のどの文字から来たかの対応
+/// # フィールド
+/// * `text` - 正規化後の文字列
+/// * `origins` - 正規化後の各バイトに対応する、正規化前の文字の範囲（開始, 終了）
+struct Normalized {
+    text: String,
+    origins: Vec<(usize, usize)>,
+}
+
+impl Normalized {
+    /// 文字（と後ろに続く濁点などの結合文字）ごとに正規化し、位置の対応を記録する
+    fn new(text: &str, config: &NormalizeConfig) -> Self {
+        let mut normalized = Self {
+            text: String::with_capacity(text.len()),
+            origins: Vec::with_capacity(text.len()),
+        };
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, next)) = chars.peek() {
+                if !is_combining(next) {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            normalize_unit(&mut normalized.text, &text[start..end], config);
+            normalized
+                .origins
+                .resize(normalized.text.len(), (start, end));
+        }
+        normalized
+    }
+}
+
+/// 直前の文字と合成される文字（結合文字、半角の濁点・半濁点）かどうか
+fn is_combining(c: char) -> bool {
+    unicode_normalization::char::is_combining_mark(c) || matches!(c, '\u{FF9E}' | '\u{FF9F}')
+}
+
+/// 1文字分（結合文字を含む）を正規化して `out` に追加する
+fn normalize_unit(out: &mut String, unit: &str, config: &NormalizeConfig) {
+    let unit: String = if config.strip_zero_width {
+        unit.chars()
+            .filter(|c| !ZERO_WIDTH_CHARS.contains(c))
+            .collect()
+    } else {
+        unit.to_string()
+    };
+    let unit: String = if config.nfkc || (config.width && is_width_variant(&unit)) {
+        unit.nfkc().collect()
+    } else {
+        unit
+    };
+    for c in unit.chars() {
+        let c = if config.kana {
+            hiragana_to_katakana(c)
+        } else {
+            c
+        };
+        if config.strip_whitespace && c.is_whitespace() {
+            continue;
+        }
+        out.push(c);
+    }
+}
+
+/// 全角・半角の文字（全角英数字、半角カタカナ、全角スペース）を含むかどうか
+///
+/// 幅の統一のみが有効な場合は、これらを含む文字だけを NFKC で通常の幅にそろえます。
+fn is_width_variant(unit: &str) -> bool {
+    unit.chars()
+        .any(|c| matches!(c, '\u{3000}' | '\u{FF01}'..='\u{FFEF}'))
+}
+
+/// ひらがなを対応するカタカナに変換する（それ以外の文字はそのまま）
+fn hiragana_to_katakana(c: char) -> char {
+    match c {
+        // ぁ〜ゖ、ゝ・ゞ はカタカナと 0x60 離れた位置に並んでいる
+        '\u{3041}'..='\u{3096}' | '\u{309D}' | '\u{309E}' => {
+            char::from_u32(c as u32 + 0x60).unwrap_or(c)
+        }
+        _ => c,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // 親モジュール(detect)の contains_samurai_phrase をインポート
+    // 親モジュール(detect)の find_phrase などをインポート
     use super::*;
 
+    /// 空白の除去以外の正規化を有効にした設定
+    const LOOSE: NormalizeConfig = NormalizeConfig {
+        nfkc: true,
+        width: true,
+        kana: true,
+        strip_zero_width: true,
+        strip_whitespace: false,
+    };
+
+    /// 1つのトリガーだけを指定した正規化・検知モードで判定する
+    fn find_phrase(
+        trigger: Trigger,
+        text: &str,
+        normalize: &NormalizeConfig,
+        mode: MatchMode,
+    ) -> Vec<TriggerMatch> {
+        let mut config = TriggersConfig::default();
+        let trigger_config = match trigger {
+            Trigger::Samurai => &mut config.samurai,
+            Trigger::Zaurus => &mut config.zaurus,
+        };
+        trigger_config.normalize = *normalize;
+        trigger_config.mode = Some(mode);
+        TriggerMatcher::new(&config)
+            .unwrap()
+            .find(text, &[])
+            .into_iter()
+            .filter(|m| m.trigger == trigger)
+            .collect()
+    }
+
+    fn contains_phrase(
+        trigger: Trigger,
+        text: &str,
+        normalize: &NormalizeConfig,
+        mode: MatchMode,
+    ) -> bool {
+        !find_phrase(trigger, text, normalize, mode).is_empty()
+    }
+
+    fn contains_samurai_phrase(text: &str) -> bool {
+        contains_phrase(
+            Trigger::Samurai,
+            text,
+            &NormalizeConfig::default(),
+            MatchMode::Anywhere,
+        )
+    }
+
+    fn contains_zaurus_phrase(text: &str) -> bool {
+        contains_phrase(
+            Trigger::Zaurus,
+            text,
+            &NormalizeConfig::default(),
+            MatchMode::Anywhere,
+        )
+    }
+
     #[test]
     fn test_samurai_detection() {
         assert!(contains_samurai_phrase("ピタッとハウス侍"));
@@ -50,4 +604,398 @@ mod tests {
         assert!(!contains_zaurus_phrase("普通の文章"));
         assert!(!contains_zaurus_phrase(""));
     }
+
+    #[test]
+    fn normalizes_each_rule_independently() {
+        let raw = NormalizeConfig::default();
+        let only = |f: fn(&mut NormalizeConfig)| {
+            let mut config = raw;
+            f(&mut config);
+            config
+        };
+        let nfkc = only(|c| c.nfkc = true);
+        let width = only(|c| c.width = true);
+        let kana = only(|c| c.kana = true);
+        let zero_width = only(|c| c.strip_zero_width = true);
+        let whitespace = only(|c| c.strip_whitespace = true);
+
+        let cases: &[(&str, &NormalizeConfig, &str)] = &[
+            // 正規化なし
+            ("ｻﾞｳﾙｽ さむらい", &raw, "ｻﾞｳﾙｽ さむらい"),
+            // NFKC: 半角カナ・全角英数字・互換文字
+            ("ｻﾞｳﾙｽ", &nfkc, "ザウルス"),
+            ("ﾊﾟﾝ", &nfkc, "パン"),
+            ("ＳＡＭＵＲＡＩ１２３", &nfkc, "SAMURAI123"),
+            ("侍\u{3000}です", &nfkc, "侍 です"),
+            ("①㍻", &nfkc, "1平成"),
+            // 幅の統一のみ: 互換文字はそのまま
+            ("ｻﾞｳﾙｽ", &width, "ザウルス"),
+            ("ＳＡＭＵＲＡＩ", &width, "SAMURAI"),
+            ("①㍻ｻﾑﾗｲ", &width, "①㍻サムライ"),
+            ("侍\u{3000}です", &width, "侍 です"),
+            // ひらがな → カタカナ
+            ("さむらい", &kana, "サムライ"),
+            ("ざうるす", &kana, "ザウルス"),
+            ("ゔぁゝゞ", &kana, "ヴァヽヾ"),
+            ("サムライ侍abc", &kana, "サムライ侍abc"),
+            // ゼロ幅文字の除去
+            (
+                "ザ\u{200B}ウ\u{200C}ル\u{200D}ス\u{2060}\u{FEFF}",
+                &zero_width,
+                "ザウルス",
+            ),
+            // 空白の除去
+            ("ザ ウ\tル\nス", &whitespace, "ザウルス"),
+            ("侍\u{3000}です", &whitespace, "侍です"),
+        ];
+        for (input, config, expected) in cases {
+            assert_eq!(
+                normalize_text(input, config),
+                *expected,
+                "{:?} with {:?}",
+                input,
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn detects_spelling_variants() {
+        let default = NormalizeConfig::default();
+        let loose = NormalizeConfig {
+            strip_whitespace: true,
+            ..LOOSE
+        };
+        let cases: &[(Trigger, &str, &NormalizeConfig, bool)] = &[
+            // 既定の設定（正規化なし）: 完全一致のみ
+            (Trigger::Samurai, "侍", &default, true),
+            (Trigger::Samurai, "サムライ", &default, false),
+            (Trigger::Samurai, "さむらい", &default, false),
+            (Trigger::Zaurus, "ｻﾞｳﾙｽ", &default, false),
+            (Trigger::Zaurus, "ザ\u{200B}ウルス", &default, false),
+            // NFKC・カナ・ゼロ幅の除去を有効にした設定
+            (Trigger::Samurai, "さむらい", &LOOSE, true),
+            (Trigger::Samurai, "サムライ", &LOOSE, true),
+            (Trigger::Samurai, "ｻﾑﾗｲ", &LOOSE, true),
+            (Trigger::Samurai, "サむらイ", &LOOSE, true),
+            (Trigger::Samurai, "侍\u{200B}", &LOOSE, true),
+            (Trigger::Samurai, "さむ らい", &LOOSE, false),
+            (Trigger::Samurai, "寺", &LOOSE, false),
+            (Trigger::Zaurus, "ｻﾞｳﾙｽ", &LOOSE, true),
+            (Trigger::Zaurus, "ざうるす", &LOOSE, true),
+            (Trigger::Zaurus, "ザ\u{200B}ウルス", &LOOSE, true),
+            (Trigger::Zaurus, "ザ ウ ル ス", &LOOSE, false),
+            (Trigger::Zaurus, "サウルス", &LOOSE, false),
+            // さらに空白の除去を有効にした設定
+            (Trigger::Zaurus, "ザ ウ ル ス", &loose, true),
+            (Trigger::Zaurus, "ｻﾞ ｳ ﾙ ｽ", &loose, true),
+            (Trigger::Samurai, "さ む ら い", &loose, true),
+        ];
+        for (trigger, text, config, expected) in cases {
+            assert_eq!(
+                contains_phrase(*trigger, text, config, MatchMode::Anywhere),
+                *expected,
+                "{:?} {:?} with {:?}",
+                trigger,
+                text,
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn match_modes() {
+        use MatchMode::*;
+        // (文章, anywhere, word_final, message_final, line_final)
+        let cases: &[(&str, [bool; 4])] = &[
+            ("侍", [true, true, true, true]),
+            ("ゲームしたい侍", [true, true, true, true]),
+            ("ゲームしたい侍！！", [true, true, true, true]),
+            ("ゲームしたい侍 😂🔥", [true, true, true, true]),
+            ("ゲームしたい侍。\n", [true, true, true, true]),
+            ("侍ジャパン", [true, false, false, false]),
+            ("侍の歴史について", [true, false, false, false]),
+            ("侍123", [true, false, false, false]),
+            ("侍、ジャパン", [true, true, false, false]),
+            ("侍 ジャパン", [true, true, false, false]),
+            ("ゲームしたい侍\nよろしく", [true, true, false, true]),
+            ("ゲームしたい侍！\nよろしく", [true, true, false, true]),
+            ("侍ジャパン\nゲームしたい侍", [true, true, true, true]),
+            ("サムライ", [true, true, true, true]),
+            ("サムライ映画", [true, false, false, false]),
+            ("普通の文章", [false, false, false, false]),
+        ];
+        // 「サムライ」はカナの表記ゆれを有効にした場合のみ検知する
+        let kana = NormalizeConfig {
+            kana: true,
+            ..NormalizeConfig::default()
+        };
+        for (text, expected) in cases {
+            for (mode, expected) in [Anywhere, WordFinal, MessageFinal, LineFinal]
+                .into_iter()
+                .zip(expected)
+            {
+                assert_eq!(
+                    contains_phrase(Trigger::Samurai, text, &kana, mode),
+                    *expected,
+                    "{:?} with {:?}",
+                    text,
+                    mode
+                );
+            }
+        }
+        assert!(contains_phrase(
+            Trigger::Zaurus,
+            "うおーザウルス！",
+            &NormalizeConfig::default(),
+            MessageFinal
+        ));
+    }
+
+    #[test]
+    fn strips_discord_markdown() {
+        let ignore = MarkdownConfig::default();
+        let reveal = MarkdownConfig {
+            ignore_spoilers: false,
+        };
+        let cases: &[(&str, &MarkdownConfig, &str)] = &[
+            ("ゲームしたい侍", &ignore, "ゲームしたい侍"),
+            // コードブロック・インラインコード
+            ("前```rust\nlet 侍 = 1;\n```後", &ignore, "前 後"),
+            ("`侍` と ``a`侍`b``", &ignore, "  と  "),
+            ("`閉じていない侍", &ignore, "`閉じていない侍"),
+            // 引用
+            ("> 侍と言った\n返信", &ignore, " \n返信"),
+            ("本文\n>>> 侍\n続き", &ignore, "本文\n "),
+            ("a > 侍", &ignore, "a > 侍"),
+            // ネタバレ
+            ("||侍||です", &ignore, " です"),
+            ("||侍||です", &reveal, "侍です"),
+            // URL・カスタム絵文字・メンション
+            ("https://example.com/侍 見て", &ignore, "  見て"),
+            ("<:侍:123>ゲーム <:侍:abc>", &ignore, " ゲーム <:侍:abc>"),
+            ("<@123> <@!456> <@&789> <#1011>", &ignore, "       "),
+            // コードの中の記法はコードごと取り除く
+            ("`||侍||` > x", &ignore, "  > x"),
+        ];
+        for (input, config, expected) in cases {
+            assert_eq!(
+                Stripped::new(input, config).text,
+                *expected,
+                "{:?} with {:?}",
+                input,
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_triggers_inside_markdown() {
+        let detect = |text: &str, mode| {
+            contains_phrase(
+                Trigger::Samurai,
+                &Stripped::new(text, &MarkdownConfig::default()).text,
+                &NormalizeConfig::default(),
+                mode,
+            )
+        };
+        assert!(!detect("```\n侍\n```", MatchMode::Anywhere));
+        assert!(!detect("> 侍\nそうだね", MatchMode::Anywhere));
+        assert!(!detect(
+            "https://ja.wikipedia.org/wiki/侍",
+            MatchMode::Anywhere
+        ));
+        assert!(!detect("||侍||", MatchMode::Anywhere));
+        assert!(detect(
+            "ゲームしたい侍 https://example.com",
+            MatchMode::MessageFinal
+        ));
+        assert!(detect(
+            "ゲームしたい侍 <:kasu:123>",
+            MatchMode::MessageFinal
+        ));
+        assert!(detect("侍`code`ジャパン", MatchMode::WordFinal));
+    }
+
+    #[test]
+    fn reports_positions_in_original_text() {
+        let mut config = TriggersConfig::default();
+        config.zaurus.normalize.nfkc = true;
+        let matcher = TriggerMatcher::new(&config).unwrap();
+        let text = "ｻﾞｳﾙｽと侍とザウルスと侍";
+        let found: Vec<(Trigger, &str)> = matcher
+            .find(text, &[])
+            .iter()
+            .map(|m| (m.trigger, &text[m.start..m.end]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Trigger::Zaurus, "ｻﾞｳﾙｽ"),
+                (Trigger::Samurai, "侍"),
+                (Trigger::Zaurus, "ザウルス"),
+                (Trigger::Samurai, "侍"),
+            ]
+        );
+
+        // 空白・ゼロ幅文字を取り除いても、元の文字列での範囲を返す
+        let loose = NormalizeConfig {
+            strip_whitespace: true,
+            ..LOOSE
+        };
+        let text = "あ ざ う\u{200B}る す！";
+        let found = find_phrase(Trigger::Zaurus, text, &loose, MatchMode::Anywhere);
+        assert_eq!(found.len(), 1);
+        assert_eq!(&text[found[0].start..found[0].end], "ざ う\u{200B}る す");
+
+        // 取り除いた記法・カスタム絵文字のあとでも、元の本文での範囲を返す
+        let mut config = TriggersConfig::default();
+        config.samurai.emojis = vec!["kasu".to_string()];
+        let matcher = TriggerMatcher::new(&config).unwrap();
+        let text = "<:kasu:123> `コード` ||秘密|| https://example.com <@456> 侍だ";
+        let found: Vec<(Trigger, &str)> = matcher
+            .find(text, &[])
+            .iter()
+            .map(|m| (m.trigger, &text[m.start..m.end]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(Trigger::Samurai, "<:kasu:123>"), (Trigger::Samurai, "侍")]
+        );
+    }
+
+    #[test]
+    fn finds_triggers_with_per_trigger_modes() {
+        let mut config = TriggersConfig::default();
+        config.samurai.mode = Some(MatchMode::MessageFinal);
+        let matcher = TriggerMatcher::new(&config).unwrap();
+        let text = "侍ジャパンとザウルスの侍";
+        let found: Vec<(Trigger, usize)> = matcher
+            .find(text, &[])
+            .iter()
+            .map(|m| (m.trigger, m.start))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Trigger::Zaurus, "侍ジャパンと".len()),
+                (Trigger::Samurai, "侍ジャパンとザウルスの".len()),
+            ]
+        );
+        assert!(matcher.find("`侍`", &[]).is_empty());
+    }
+
+    #[test]
+    fn finds_configured_phrases_and_patterns() {
+        let mut config = TriggersConfig::default();
+        config.samurai.phrases = vec!["もののふ".to_string(), "さむらい".to_string()];
+        config.zaurus.patterns = vec!["ザ+ウルス".to_string(), "ティラノ(サウルス)?".to_string()];
+        config.samurai.normalize.nfkc = true;
+        config.samurai.normalize.kana = true;
+        let matcher = TriggerMatcher::new(&config).unwrap();
+
+        let text = "ﾓﾉﾉﾌとザザザウルスとてぃらのとティラノサウルスとサムライ";
+        let found: Vec<(Trigger, &str)> = matcher
+            .find(text, &[])
+            .iter()
+            .map(|m| (m.trigger, &text[m.start..m.end]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Trigger::Samurai, "ﾓﾉﾉﾌ"),
+                // 語句「ザウルス」と正規表現の両方に一致しても、範囲ごとに1件ずつ
+                (Trigger::Zaurus, "ザザザウルス"),
+                (Trigger::Zaurus, "ザウルス"),
+                (Trigger::Zaurus, "ティラノサウルス"),
+                (Trigger::Samurai, "サムライ"),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_emoji_sticker_and_reaction_triggers() {
+        let mut config = TriggersConfig::default();
+        config.samurai.emojis = vec![":kasu:".to_string()];
+        config.samurai.stickers = vec!["侍スタンプ".to_string()];
+        config.samurai.reactions = vec!["kasu".to_string(), "⚔️".to_string()];
+        config.zaurus.emojis = vec!["dino".to_string()];
+        config.zaurus.mode = Some(MatchMode::MessageFinal);
+        let matcher = TriggerMatcher::new(&config).unwrap();
+
+        // カスタム絵文字には検知モードを適用せず、コードの中の絵文字は対象外
+        let text = "<@1> <a:dino:2> ザウルスです `<:kasu:3>` <:kasu:4>";
+        let found: Vec<(Trigger, usize)> = matcher
+            .find(
+                text,
+                &["侍スタンプ".to_string(), "別のスタンプ".to_string()],
+            )
+            .iter()
+            .map(|m| (m.trigger, m.start))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Trigger::Zaurus, text.find("<a:dino:2>").unwrap()),
+                (Trigger::Samurai, text.rfind("<:kasu:4>").unwrap()),
+                (Trigger::Samurai, text.len()),
+            ]
+        );
+
+        let reaction = |emoji| matcher.find_reaction(&emoji);
+        assert_eq!(
+            reaction(Emoji::Custom("kasu".to_string())),
+            vec![Trigger::Samurai]
+        );
+        assert_eq!(
+            reaction(Emoji::Unicode("⚔️".to_string())),
+            vec![Trigger::Samurai]
+        );
+        assert!(reaction(Emoji::Custom("dino".to_string())).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let mut config = TriggersConfig::default();
+        config.samurai.patterns = vec!["侍(".to_string()];
+        let error = TriggerMatcher::new(&config).unwrap_err();
+        assert!(format!("{:#}", error).contains("samurai"));
+    }
+
+    /// 長いメッセージでの検知のスループットを測る
+    ///
+    /// `cargo test --release bench_long_messages -- --ignored --nocapture` で実行します。
+    #[test]
+    #[ignore]
+    fn bench_long_messages() {
+        use std::time::Instant;
+
+        let mut config = TriggersConfig::default();
+        config.samurai.phrases = (0..40).map(|i| format!("侍{}号", i)).collect();
+        config.zaurus.phrases = (0..40).map(|i| format!("ザウルス{}世", i)).collect();
+        config.zaurus.patterns = (2..12).map(|i| format!("ザ{{{}}}ウルス", i)).collect();
+        let matcher = TriggerMatcher::new(&config).unwrap();
+
+        // URL の中の語句は取り除くので、1回の繰り返しにつき侍が1件
+        let text = "今日はいい天気ですね。ゲームしたい侍🔥 https://example.com/侍 <:kasu:123> "
+            .repeat(200)
+            + "ザウルス";
+        let iterations = 200;
+        let started = Instant::now();
+        let mut found = 0;
+        for _ in 0..iterations {
+            found += matcher.find(&text, &[]).len();
+        }
+        let elapsed = started.elapsed().as_secs_f64();
+        let megabytes = (text.len() * iterations) as f64 / 1_000_000.0;
+        println!(
+            "{} bytes x {}: {:.1} MB/s, {:.1} µs/message ({} matches)",
+            text.len(),
+            iterations,
+            megabytes / elapsed,
+            elapsed * 1_000_000.0 / iterations as f64,
+            found / iterations
+        );
+        assert_eq!(found / iterations, 201);
+    }
 }
@@ -1,82 +1,546 @@
-use log::{error, info};
-use serenity::all::ReactionType;
+use crate::commands::COMMANDS;
+use crate::metrics::METRICS;
+use crate::platform::{
+    Button, ChatPlatform, CommandInvocation, ComponentPress, Embed, Emoji, IncomingMessage,
+    InteractionResponder, MessageRef,
+};
+use anyhow::{Context as _, Result, anyhow};
+use log::info;
+use serenity::all::{
+    ButtonStyle, ChannelId, Command, CommandDataOptionValue, CommandInteraction, CommandOptionType,
+    ComponentInteraction, CreateActionRow, CreateAllowedMentions, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, EditMessage, GuildId, MessageId,
+    MessageUpdateEvent, Reaction, ReactionType,
+};
+use serenity::async_trait;
 use serenity::client::Context;
 use serenity::model::channel::Message;
 
-/// リアクションを追加する関数
-/// # 引数
-/// * `ctx` - コンテキスト (メッセージの送信先やボットの情報など)
-/// * `msg` - リアクションを追加するメッセージ
-pub async fn samurai_reaction(ctx: &Context, msg: &Message) {
-    // リアクションに使うカスタム絵文字の名前
-    let target_emoji_name = "kasu"; // :kasu: の名前部分
-
-    // メッセージが送信されたサーバー(Guild)のIDを取得
-    // (ダイレクトメッセージでは動作しない)
-    let guild_id = match msg.guild_id {
-        Some(id) => id,
-        None => {
-            info!("Cannot react in DMs.");
-            return;
-        }
-    };
-
-    // (1) Guild ID を使って、HTTP で Guild 情報を取得
-    let http_guild = match ctx.http.get_guild(guild_id).await {
-        Ok(g) => g,
-        Err(why) => {
-            error!("Failed to fetch guild via HTTP: {:?}", why);
-            return;
+impl From<&Message> for IncomingMessage {
+    fn from(msg: &Message) -> Self {
+        Self {
+            id: msg.id.get(),
+            channel_id: msg.channel_id.get(),
+            guild_id: msg.guild_id.map(|id| id.get()),
+            category_id: None,
+            author_id: msg.author.id.get(),
+            author_name: msg.author.name.clone(),
+            author_bot: msg.author.bot,
+            role_ids: msg
+                .member
+                .as_ref()
+                .map(|m| m.roles.iter().map(|r| r.get()).collect())
+                .unwrap_or_default(),
+            content: msg.content.clone(),
+            stickers: msg.sticker_items.iter().map(|s| s.name.clone()).collect(),
         }
-    };
-    let guild_name = http_guild.name.clone();
+    }
+}
 
-    // (2) Guild の絵文字を取得
-    let emoji_opt = http_guild
-        .emojis
-        .values() // &Emoji のイテレータ
-        .find(|e| e.name == target_emoji_name)
-        .cloned(); // Emoji をクローン
+/// 埋め込みを serenity の形式に変換する関数
+fn create_embed(embed: &Embed) -> CreateEmbed {
+    let mut builder = CreateEmbed::new()
+        .title(&embed.title)
+        .description(&embed.description)
+        .color(embed.color);
+    if let Some(footer) = &embed.footer {
+        builder = builder.footer(CreateEmbedFooter::new(footer));
+    }
+    if let Some(url) = &embed.thumbnail_url {
+        builder = builder.thumbnail(url);
+    }
+    if let Some(url) = &embed.image_url {
+        builder = builder.image(url);
+    }
+    builder
+}
 
-    if let Some(emoji) = emoji_opt {
-        // (3) メッセージにリアクションを追加
-        let reaction = ReactionType::from(emoji.clone());
-        info!("Found emoji: {} (ID: {})", emoji.name, emoji.id);
+/// ボタンを1行のアクション行に変換する関数（ボタンがなければ空）
+fn create_components(buttons: &[Button]) -> Vec<CreateActionRow> {
+    if buttons.is_empty() {
+        return Vec::new();
+    }
+    let row = buttons
+        .iter()
+        .map(|b| {
+            CreateButton::new(&b.custom_id)
+                .label(&b.label)
+                .style(ButtonStyle::Secondary)
+        })
+        .collect();
+    vec![CreateActionRow::Buttons(row)]
+}
+
+/// serenity のボタン押下インタラクションを変換する関数
+/// # 引数
+/// * `interaction` - ボタン押下のインタラクション
+/// # 戻り値
+/// * `ComponentPress` - プラットフォーム非依存のボタン押下
+pub fn component_press(interaction: &ComponentInteraction) -> ComponentPress {
+    ComponentPress {
+        custom_id: interaction.data.custom_id.clone(),
+        message_id: interaction.message.id.get(),
+        channel_id: interaction.channel_id.get(),
+        guild_id: interaction.guild_id.map(|id| id.get()),
+        user_id: interaction.user.id.get(),
+        user_name: interaction.user.name.clone(),
+    }
+}
 
-        if let Err(why) = msg.react(&ctx.http, reaction).await {
-            error!("Error reacting to message {}: {:?}", msg.id, why);
-        } else {
-            info!(
-                "Successfully reacted with :{}: to message {}",
-                target_emoji_name, msg.id
+/// serenity のスラッシュコマンドのインタラクションを変換する関数
+/// # 引数
+/// * `interaction` - スラッシュコマンドのインタラクション
+/// # 戻り値
+/// * `CommandInvocation` - プラットフォーム非依存のコマンド呼び出し
+pub fn command_invocation(interaction: &CommandInteraction) -> CommandInvocation {
+    let subcommand = interaction
+        .data
+        .options
+        .iter()
+        .find(|o| matches!(o.value, CommandDataOptionValue::SubCommand(_)))
+        .map(|o| o.name.clone());
+    let options = interaction
+        .data
+        .options
+        .iter()
+        .filter_map(|o| match &o.value {
+            CommandDataOptionValue::String(value) => Some((o.name.clone(), value.clone())),
+            _ => None,
+        })
+        .collect();
+    CommandInvocation {
+        name: interaction.data.name.clone(),
+        subcommand,
+        options,
+        channel_id: interaction.channel_id.get(),
+        guild_id: interaction.guild_id.map(|id| id.get()),
+        user_id: interaction.user.id.get(),
+        user_name: interaction.user.name.clone(),
+    }
+}
+
+/// スラッシュコマンドを登録する関数（グローバルコマンドとして上書き登録する）
+/// # 引数
+/// * `ctx` - コンテキスト
+pub async fn register_commands(ctx: &Context) -> Result<()> {
+    let commands = COMMANDS
+        .iter()
+        .map(|spec| {
+            let command = spec.subcommands.iter().fold(
+                CreateCommand::new(spec.name).description(spec.description),
+                |command, (name, description)| {
+                    command.add_option(CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        *name,
+                        *description,
+                    ))
+                },
             );
+            spec.options.iter().fold(command, |command, option| {
+                let created = option.choices.iter().fold(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        option.name,
+                        option.description,
+                    ),
+                    |created, (name, value)| created.add_string_choice(*name, *value),
+                );
+                command.add_option(created)
+            })
+        })
+        .collect();
+    Command::set_global_commands(&ctx.http, commands)
+        .await
+        .context("Failed to register slash commands")?;
+    Ok(())
+}
+
+/// 応答対象の serenity のインタラクション
+enum InteractionTarget {
+    Component(Box<ComponentInteraction>),
+    Command(Box<CommandInteraction>),
+}
+
+/// serenity のインタラクションに応答する InteractionResponder の実装
+pub struct SerenityInteraction {
+    ctx: Context,
+    target: InteractionTarget,
+}
+
+impl SerenityInteraction {
+    /// ボタン押下に応答する SerenityInteraction を作成する関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `interaction` - 応答対象のインタラクション
+    pub fn component(ctx: &Context, interaction: ComponentInteraction) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            target: InteractionTarget::Component(Box::new(interaction)),
+        }
+    }
+
+    /// スラッシュコマンドに応答する SerenityInteraction を作成する関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `interaction` - 応答対象のインタラクション
+    pub fn command(ctx: &Context, interaction: CommandInteraction) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            target: InteractionTarget::Command(Box::new(interaction)),
         }
-    } else {
-        error!(
-            "Error: Custom emoji ':{}:' not found in guild '{}' (ID: {}).",
-            target_emoji_name, guild_name, guild_id
-        );
+    }
+
+    async fn respond(&self, response: CreateInteractionResponse) -> Result<()> {
+        let (id, result) = match &self.target {
+            InteractionTarget::Component(i) => {
+                (i.id, i.create_response(&self.ctx.http, response).await)
+            }
+            InteractionTarget::Command(i) => {
+                (i.id, i.create_response(&self.ctx.http, response).await)
+            }
+        };
+        result.with_context(|| format!("Error responding to interaction {}", id))
+    }
+}
+
+#[async_trait]
+impl InteractionResponder for SerenityInteraction {
+    /// ボタンが付いているメッセージの埋め込みとボタンを差し替える関数
+    async fn update_embed(&self, embed: &Embed, buttons: &[Button]) -> Result<()> {
+        let message = CreateInteractionResponseMessage::new()
+            .embed(create_embed(embed))
+            .components(create_components(buttons));
+        self.respond(CreateInteractionResponse::UpdateMessage(message))
+            .await
+    }
+
+    /// 埋め込みのメッセージで応答する関数
+    async fn respond_embed(&self, embed: &Embed) -> Result<()> {
+        let message = CreateInteractionResponseMessage::new().embed(create_embed(embed));
+        self.respond(CreateInteractionResponse::Message(message))
+            .await
+    }
+
+    /// 押したユーザーにだけ見えるメッセージで応答する関数
+    async fn respond_ephemeral(&self, content: &str) -> Result<()> {
+        let message = CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true);
+        self.respond(CreateInteractionResponse::Message(message))
+            .await
     }
 }
 
-/// メッセージに返信する関数
+/// メッセージ編集イベントを変換する関数
+///
+/// 本文や送信者が含まれないイベント（埋め込みの展開など）の場合は `None` を返します。
 /// # 引数
-/// * `ctx` - コンテキスト (メッセージの送信先やボットの情報など)
-/// * `msg` - 返信するメッセージ
-/// * `content` - 返信内容
-pub async fn samurai_reply(ctx: &Context, msg: &Message, content: &str) {
-    if let Err(why) = msg.reply(ctx, content).await {
-        error!("Error replying to message {}: {:?}", msg.id, why);
+/// * `ctx` - コンテキスト (キャッシュの参照に使用)
+/// * `event` - メッセージ編集イベント
+/// # 戻り値
+/// * `Option<IncomingMessage>` - 編集後のメッセージ
+pub fn incoming_message_update(
+    ctx: &Context,
+    event: &MessageUpdateEvent,
+) -> Option<IncomingMessage> {
+    let author = event.author.as_ref()?;
+    let content = event.content.clone()?;
+    let role_ids = event
+        .member
+        .as_ref()
+        .and_then(|m| m.as_ref())
+        .map(|m| m.roles.iter().map(|r| r.get()).collect())
+        .unwrap_or_default();
+
+    Some(IncomingMessage {
+        id: event.id.get(),
+        channel_id: event.channel_id.get(),
+        guild_id: event.guild_id.map(|id| id.get()),
+        category_id: event
+            .guild_id
+            .and_then(|guild_id| category_of(ctx, guild_id, event.channel_id)),
+        author_id: author.id.get(),
+        author_name: author.name.clone(),
+        author_bot: author.bot,
+        role_ids,
+        content,
+        stickers: event
+            .sticker_items
+            .iter()
+            .flatten()
+            .map(|s| s.name.clone())
+            .collect(),
+    })
+}
+
+/// キャッシュからチャンネルが属するカテゴリIDを取得する関数
+/// （スレッドの場合は親チャンネルのカテゴリを使う）
+fn category_of(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> Option<u64> {
+    let guild = ctx.cache.guild(guild_id)?;
+    let channel = guild.channels.get(&channel_id).or_else(|| {
+        let thread = guild.threads.iter().find(|t| t.id == channel_id)?;
+        guild.channels.get(&thread.parent_id?)
+    })?;
+    channel.parent_id.map(|id| id.get())
+}
+
+/// serenity のメッセージを、キャッシュから補ったカテゴリIDを含めて変換する関数
+/// # 引数
+/// * `ctx` - コンテキスト (キャッシュの参照に使用)
+/// * `msg` - 受信したメッセージ
+/// # 戻り値
+/// * `IncomingMessage` - プラットフォーム非依存のメッセージ
+pub fn incoming_message(ctx: &Context, msg: &Message) -> IncomingMessage {
+    let mut incoming = IncomingMessage::from(msg);
+    incoming.category_id = msg
+        .guild_id
+        .and_then(|guild_id| category_of(ctx, guild_id, msg.channel_id));
+    incoming
+}
+
+/// リアクションの絵文字をプラットフォーム非依存の形式に変換する関数
+///
+/// 名前のない（削除済みなどの）カスタム絵文字の場合は `None` を返します。
+/// # 引数
+/// * `reaction` - 追加されたリアクション
+pub fn reaction_emoji(reaction: &Reaction) -> Option<Emoji> {
+    match &reaction.emoji {
+        ReactionType::Custom { name, .. } => name.clone().map(Emoji::Custom),
+        ReactionType::Unicode(unicode) => Some(Emoji::Unicode(unicode.clone())),
+        _ => None,
     }
 }
 
-/// メッセージに返信する関数（ザウルス用）
+/// リアクションが付いたメッセージを取得し、送信者をリアクションを付けたユーザーに置き換えて変換する関数
+///
+/// ボット自身が付けたリアクション（侍の検知時のリアクションなど）は、ボットからのものとして扱います。
 /// # 引数
-/// * `ctx` - コンテキスト
-/// * `msg` - 返信するメッセージ
-/// * `content` - 返信内容
-pub async fn zaurus_reply(ctx: &Context, msg: &Message, content: &str) {
-    if let Err(why) = msg.reply(ctx, content).await {
-        error!("Error replying to message {}: {:?}", msg.id, why);
+/// * `ctx` - コンテキスト (メッセージの取得とキャッシュの参照に使用)
+/// * `reaction` - 追加されたリアクション
+/// # 戻り値
+/// * `Ok(IncomingMessage)` - リアクションが付いたメッセージ
+/// * `Err(anyhow::Error)` - メッセージの取得に失敗した場合
+pub async fn incoming_reaction(ctx: &Context, reaction: &Reaction) -> Result<IncomingMessage> {
+    let message = reaction
+        .message(&ctx.http)
+        .await
+        .context("Failed to fetch reacted message")?;
+    let mut incoming = incoming_message(ctx, &message);
+
+    let user_id = reaction.user_id.context("Reaction has no user")?;
+    let member = reaction.member.as_ref();
+    incoming.author_id = user_id.get();
+    incoming.author_name = member.map(|m| m.user.name.clone()).unwrap_or_default();
+    incoming.author_bot =
+        user_id == ctx.cache.current_user().id || member.is_some_and(|m| m.user.bot);
+    incoming.role_ids = member
+        .map(|m| m.roles.iter().map(|r| r.get()).collect())
+        .unwrap_or_default();
+    Ok(incoming)
+}
+
+/// serenity の `Context` を使って Discord に副作用を送る `ChatPlatform` の実装
+pub struct SerenityPlatform {
+    ctx: Context,
+}
+
+impl SerenityPlatform {
+    /// 新しい SerenityPlatform を作成する関数
+    /// # 引数
+    /// * `ctx` - コンテキスト (メッセージの送信先やボットの情報など)
+    pub fn new(ctx: &Context) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    /// Guild のカスタム絵文字を名前で探してリアクション種別に変換する関数
+    /// # 引数
+    /// * `guild_id` - メッセージが送信されたサーバーID
+    /// * `emoji_name` - リアクションに使うカスタム絵文字の名前
+    async fn find_custom_emoji(&self, guild_id: u64, emoji_name: &str) -> Result<ReactionType> {
+        // (1) Guild ID を使って、HTTP で Guild 情報を取得
+        let http_guild = self
+            .ctx
+            .http
+            .get_guild(guild_id.into())
+            .await
+            .context("Failed to fetch guild via HTTP")?;
+
+        // (2) Guild の絵文字を取得
+        let emoji = http_guild
+            .emojis
+            .values() // &Emoji のイテレータ
+            .find(|e| e.name == emoji_name)
+            .cloned() // Emoji をクローン
+            .ok_or_else(|| {
+                anyhow!(
+                    "Custom emoji ':{}:' not found in guild '{}' (ID: {}).",
+                    emoji_name,
+                    http_guild.name,
+                    guild_id
+                )
+            })?;
+        info!("Found emoji: {} (ID: {})", emoji.name, emoji.id);
+        Ok(ReactionType::from(emoji))
+    }
+
+    /// メッセージにリアクションを追加する関数
+    /// # 引数
+    /// * `msg` - リアクションを追加するメッセージ
+    /// * `emoji` - リアクションに使う絵文字
+    async fn add_reaction(&self, msg: &IncomingMessage, emoji: &Emoji) -> Result<()> {
+        let reaction = match emoji {
+            Emoji::Custom(name) => {
+                // カスタム絵文字はサーバー内でのみ使える (ダイレクトメッセージでは動作しない)
+                let guild_id = msg
+                    .guild_id
+                    .ok_or_else(|| anyhow!("Cannot react with custom emoji :{}: in DMs.", name))?;
+                self.find_custom_emoji(guild_id, name).await?
+            }
+            Emoji::Unicode(unicode) => ReactionType::Unicode(unicode.clone()),
+        };
+
+        // (3) メッセージにリアクションを追加
+        ChannelId::new(msg.channel_id)
+            .create_reaction(&self.ctx.http, MessageId::new(msg.id), reaction)
+            .await
+            .with_context(|| format!("Error reacting to message {}", msg.id))?;
+        Ok(())
+    }
+
+    /// 返信先を設定してメッセージを送信する関数
+    /// # 引数
+    /// * `msg` - 返信対象のメッセージ
+    /// * `builder` - 本文や埋め込みを設定済みのメッセージ
+    async fn send_reply(
+        &self,
+        msg: &IncomingMessage,
+        builder: CreateMessage,
+    ) -> Result<MessageRef> {
+        let channel_id = ChannelId::new(msg.channel_id);
+        // 返信先ユーザーへのメンション通知は行わない (`Message::reply` と同等)
+        let allowed_mentions = CreateAllowedMentions::new()
+            .replied_user(false)
+            .everyone(true)
+            .all_users(true)
+            .all_roles(true);
+        let builder = builder
+            .reference_message((channel_id, MessageId::new(msg.id)))
+            .allowed_mentions(allowed_mentions);
+
+        let result = channel_id.send_message(&self.ctx.http, builder).await;
+        METRICS
+            .replies
+            .inc(if result.is_ok() { "sent" } else { "failed" });
+        let sent = result.with_context(|| format!("Error replying to message {}", msg.id))?;
+        Ok(MessageRef {
+            channel_id: sent.channel_id.get(),
+            message_id: sent.id.get(),
+        })
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for SerenityPlatform {
+    /// リアクションを追加する関数
+    /// # 引数
+    /// * `msg` - リアクションを追加するメッセージ
+    /// * `emoji` - リアクションに使う絵文字
+    async fn react(&self, msg: &IncomingMessage, emoji: &Emoji) -> Result<()> {
+        let result = self.add_reaction(msg, emoji).await;
+        METRICS
+            .reactions
+            .inc(if result.is_ok() { "added" } else { "failed" });
+        result?;
+        info!("Successfully reacted with {} to message {}", emoji, msg.id);
+        Ok(())
+    }
+
+    /// メッセージに返信する関数
+    /// # 引数
+    /// * `msg` - 返信するメッセージ
+    /// * `content` - 返信内容
+    async fn reply(&self, msg: &IncomingMessage, content: &str) -> Result<MessageRef> {
+        self.send_reply(msg, CreateMessage::new().content(content))
+            .await
+    }
+
+    /// メッセージに埋め込みで返信する関数
+    /// # 引数
+    /// * `msg` - 返信対象のメッセージ
+    /// * `embed` - 返信する埋め込み
+    /// # 戻り値
+    /// * `Ok(MessageRef)` - 送信した返信メッセージ
+    async fn reply_embed(
+        &self,
+        msg: &IncomingMessage,
+        embed: &Embed,
+        buttons: &[Button],
+    ) -> Result<MessageRef> {
+        let builder = CreateMessage::new()
+            .embed(create_embed(embed))
+            .components(create_components(buttons));
+        self.send_reply(msg, builder).await
+    }
+
+    /// チャンネルに埋め込みを投稿する関数
+    /// # 引数
+    /// * `channel_id` - 投稿先のチャンネルID
+    /// * `embed` - 投稿する埋め込み
+    /// # 戻り値
+    /// * `Ok(MessageRef)` - 投稿したメッセージ
+    async fn send_embed(&self, channel_id: u64, embed: &Embed) -> Result<MessageRef> {
+        let sent = ChannelId::new(channel_id)
+            .send_message(
+                &self.ctx.http,
+                CreateMessage::new().embed(create_embed(embed)),
+            )
+            .await
+            .with_context(|| format!("Error posting to channel {}", channel_id))?;
+        Ok(MessageRef {
+            channel_id: sent.channel_id.get(),
+            message_id: sent.id.get(),
+        })
+    }
+
+    /// 送信済みのメッセージを編集する関数
+    /// # 引数
+    /// * `target` - 編集対象のメッセージ
+    /// * `content` - 新しい内容
+    async fn edit(&self, target: MessageRef, content: &str) -> Result<()> {
+        ChannelId::new(target.channel_id)
+            .edit_message(
+                &self.ctx.http,
+                MessageId::new(target.message_id),
+                EditMessage::new()
+                    .content(content)
+                    .embeds(Vec::new())
+                    .components(Vec::new()),
+            )
+            .await
+            .with_context(|| format!("Error editing message {}", target.message_id))?;
+        Ok(())
+    }
+
+    /// 送信済みのメッセージを削除する関数
+    /// # 引数
+    /// * `target` - 削除対象のメッセージ
+    async fn delete(&self, target: MessageRef) -> Result<()> {
+        ChannelId::new(target.channel_id)
+            .delete_message(&self.ctx.http, MessageId::new(target.message_id))
+            .await
+            .with_context(|| format!("Error deleting message {}", target.message_id))?;
+        Ok(())
+    }
+
+    /// 入力中インジケーターを表示する関数
+    /// # 引数
+    /// * `channel_id` - 対象チャンネルID
+    async fn typing(&self, channel_id: u64) -> Result<()> {
+        ChannelId::new(channel_id)
+            .broadcast_typing(&self.ctx.http)
+            .await
+            .with_context(|| format!("Error broadcasting typing to channel {}", channel_id))?;
+        Ok(())
     }
 }
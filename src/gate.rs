@@ -0,0 +1,132 @@
+use crate::config::{GateConfig, TimeWindow};
+use crate::stats::{self, JST_OFFSET_SECS, SECS_PER_DAY};
+use std::cmp::Ordering;
+
+/// トリガーが応答する条件（曜日・時間帯・確率）を満たすかどうかを判定する関数
+///
+/// 曜日・時間帯・休止時間帯を先に判定し、すべて満たした場合だけ確率を判定します
+/// （確率が 1.0 以上の場合は乱数を使いません）。
+/// # 引数
+/// * `config` - トリガーの応答条件
+/// * `now` - 現在の UNIX 時刻（秒）
+/// * `roll` - 0.0 以上 1.0 未満の乱数を返す関数
+/// # 戻り値
+/// * `true` - 応答してよい場合
+/// * `false` - 応答しない場合
+pub fn is_open(config: &GateConfig, now: u64, roll: impl FnOnce() -> f64) -> bool {
+    // --- 曜日（Weekday は月曜日が 0 の順に並んでいる） ---
+    let weekday = stats::weekday(stats::jst_day(now));
+    if !config.days.is_empty() && !config.days.iter().any(|day| *day as u32 == weekday) {
+        return false;
+    }
+
+    // --- 時間帯・休止時間帯 ---
+    let secs = (now + JST_OFFSET_SECS) % SECS_PER_DAY;
+    if config
+        .active_hours
+        .is_some_and(|window| !contains(window, secs))
+    {
+        return false;
+    }
+    if config
+        .quiet_hours
+        .iter()
+        .any(|window| contains(*window, secs))
+    {
+        return false;
+    }
+
+    // --- 確率 ---
+    config.probability >= 1.0 || (config.probability > 0.0 && roll() < config.probability)
+}
+
+/// 0時からの秒数が時間帯に含まれるかどうか（日をまたぐ時間帯にも対応）
+fn contains(window: TimeWindow, secs: u64) -> bool {
+    match window.start.cmp(&window.end) {
+        Ordering::Equal => true,
+        Ordering::Less => window.start <= secs && secs < window.end,
+        Ordering::Greater => secs >= window.start || secs < window.end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Weekday;
+
+    /// 2024-01-03（水）12:00 JST
+    const WEDNESDAY_NOON: u64 = 1_704_250_800;
+    const HOUR: u64 = 60 * 60;
+
+    fn window(value: &str) -> TimeWindow {
+        TimeWindow::try_from(value.to_string()).unwrap()
+    }
+
+    #[test]
+    fn checks_days_and_hours_in_jst() {
+        let gate = |f: fn(&mut GateConfig)| {
+            let mut config = GateConfig::default();
+            f(&mut config);
+            config
+        };
+        let always = GateConfig::default();
+        let weekdays = gate(|c| c.days = vec![Weekday::Mon, Weekday::Wed]);
+        let weekend = gate(|c| c.days = vec![Weekday::Sat, Weekday::Sun]);
+        let daytime = gate(|c| c.active_hours = Some(window("09:00-18:00")));
+        let night = gate(|c| c.active_hours = Some(window("22:00-06:00")));
+        let lunch = gate(|c| c.quiet_hours = vec![window("12:00-13:00")]);
+
+        // (条件, 時刻, 応答するか)
+        let cases: &[(&GateConfig, u64, bool)] = &[
+            (&always, WEDNESDAY_NOON, true),
+            (&weekdays, WEDNESDAY_NOON, true),
+            (&weekend, WEDNESDAY_NOON, false),
+            // 水曜日の 23:59 まで水曜日、0:00 から木曜日
+            (&weekdays, WEDNESDAY_NOON + 12 * HOUR - 1, true),
+            (&weekdays, WEDNESDAY_NOON + 12 * HOUR, false),
+            (&daytime, WEDNESDAY_NOON, true),
+            (&daytime, WEDNESDAY_NOON - 3 * HOUR, true),
+            (&daytime, WEDNESDAY_NOON - 3 * HOUR - 1, false),
+            (&daytime, WEDNESDAY_NOON + 6 * HOUR, false),
+            // 日をまたぐ時間帯
+            (&night, WEDNESDAY_NOON, false),
+            (&night, WEDNESDAY_NOON + 11 * HOUR, true),
+            (&night, WEDNESDAY_NOON + 17 * HOUR, true),
+            (&night, WEDNESDAY_NOON + 18 * HOUR, false),
+            (&lunch, WEDNESDAY_NOON, false),
+            (&lunch, WEDNESDAY_NOON + HOUR, true),
+        ];
+        for (config, now, expected) in cases {
+            assert_eq!(
+                is_open(config, *now, || panic!("probability is not rolled")),
+                *expected,
+                "{:?} at {}",
+                config,
+                now
+            );
+        }
+    }
+
+    #[test]
+    fn rolls_probability_only_when_needed() {
+        let config = GateConfig {
+            probability: 0.3,
+            ..Default::default()
+        };
+        assert!(is_open(&config, WEDNESDAY_NOON, || 0.29));
+        assert!(!is_open(&config, WEDNESDAY_NOON, || 0.3));
+
+        let never = GateConfig {
+            probability: 0.0,
+            ..Default::default()
+        };
+        assert!(!is_open(&never, WEDNESDAY_NOON, || panic!("not rolled")));
+
+        // 時間帯の外では確率を判定しない
+        let quiet = GateConfig {
+            quiet_hours: vec![window("00:00-00:00")],
+            ..config
+        };
+        assert!(!is_open(&quiet, WEDNESDAY_NOON, || panic!("not rolled")));
+    }
+}
@@ -0,0 +1,1497 @@
+use crate::access;
+use crate::buttons;
+use crate::chat::ChatBackend;
+use crate::clock::Clock;
+use crate::collection::Collection;
+use crate::config::{
+    ButtonsConfig, DailyConfig, DmConfig, EditsConfig, ReplyStyle, ResolutionPolicy,
+    SamuraiReplyConfig, TemplatesConfig, TriggerRemovedAction, TriggersConfig,
+};
+use crate::cooldown::{CooldownDecision, Cooldowns};
+use crate::detect::{Trigger, TriggerMatch, TriggerMatcher};
+use crate::gate;
+use crate::health::HealthState;
+use crate::memory::ConversationMemory;
+use crate::metrics::METRICS;
+use crate::platform::{ChatPlatform, Embed, Emoji, IncomingMessage, MessageRef};
+use crate::random::SharedRng;
+use crate::replies::ReplyTracker;
+use crate::shutdown::Shutdown;
+use crate::stats::TriggerStats;
+use crate::table::{self, SamuraiEntry};
+use crate::template;
+use log::{error, info};
+use rand::Rng;
+use std::sync::Arc;
+
+/// 侍検知時にリアクションで使うカスタム絵文字の名前
+pub const SAMURAI_EMOJI_NAME: &str = "kasu"; // :kasu: の名前部分
+
+/// ボタン付きの埋め込みで、説明を省略せずに表示する最大文字数
+const DESCRIPTION_PREVIEW_CHARS: usize = 200;
+
+/// メッセージ処理に必要な依存をまとめた構造体
+/// # フィールド
+/// * `chat` - ザウルス応答に使うチャットバックエンド
+/// * `samurai_entries` - 侍データのテーブル
+/// * `shutdown` - シャットダウン状態（処理中メッセージの追跡）
+/// * `health` - 稼働状態（ヘルスチェック用）
+/// * `triggers` - トリガーごとの動作設定
+/// * `matcher` - `triggers` の語句・正規表現をコンパイルした検知器
+/// * `cooldowns` - トリガーごとのクールダウン状態
+/// * `dm` - DM モードの設定
+/// * `memory` - DM でのユーザーごとの会話履歴
+/// * `edits` - メッセージ編集時の動作設定
+/// * `replies` - 元メッセージとボットの返信の対応
+/// * `samurai_reply` - 侍の返信の表示設定
+/// * `templates` - 返信テンプレート
+/// * `buttons` - 侍の返信に付けるボタンの設定
+/// * `rerolls` - 「もう一回」ボタンのユーザーごとの回数制限
+/// * `collection` - ユーザーごとの侍コレクション（引いた侍とお気に入り）
+/// * `stats` - サーバーごと・ユーザーごとのトリガー回数（ランキング用）
+/// * `daily` - 「今日の侍」の定期投稿の設定
+/// * `clock` - 現在時刻（応答条件の時間帯・曜日の判定、ランキング、ボタンの有効期限、今日の侍に使う）
+/// * `rng` - 乱数生成器（侍の抽選と応答条件の確率の判定に使う）
+pub struct HandlerDeps {
+    pub chat: Arc<dyn ChatBackend>,
+    pub samurai_entries: Arc<Vec<SamuraiEntry>>,
+    pub shutdown: Arc<Shutdown>,
+    pub health: Arc<HealthState>,
+    pub triggers: TriggersConfig,
+    pub matcher: TriggerMatcher,
+    pub cooldowns: Cooldowns,
+    pub dm: DmConfig,
+    pub memory: ConversationMemory,
+    pub edits: EditsConfig,
+    pub replies: ReplyTracker,
+    pub samurai_reply: SamuraiReplyConfig,
+    pub templates: TemplatesConfig,
+    pub buttons: ButtonsConfig,
+    pub rerolls: Cooldowns,
+    pub collection: Collection,
+    pub stats: TriggerStats,
+    pub daily: DailyConfig,
+    pub clock: Arc<dyn Clock>,
+    pub rng: SharedRng,
+}
+
+/// 受信メッセージを判定し、必要なリアクション・返信を行う関数
+///
+/// Discord などの副作用はすべて `platform` 越しに行うため、
+/// ゲートウェイに接続せずにテストできます。
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `platform` - リアクションや返信を送るプラットフォーム
+/// * `msg` - 受信したメッセージ
+pub async fn handle_message(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+) {
+    if !should_handle(deps, msg) {
+        return;
+    }
+
+    // --- シャットダウン中であれば新しいトリガーを受け付けない ---
+    // ガードが生きている間は処理中として数えられ、シャットダウン時に完了を待たれる
+    let Some(_in_flight) = deps.shutdown.try_begin() else {
+        info!("Shutting down; ignoring message: {}", msg.id);
+        return;
+    };
+
+    // --- メッセージの内容（本文・絵文字・スタンプ）から侍を検出 ---
+    let found = distinct_triggers(&deps.matcher.find(&msg.content, &msg.stickers));
+    for trigger in &found {
+        METRICS.triggers_matched.inc(trigger.name());
+    }
+    let triggers = resolve_triggers(deps, msg, found);
+    claim_and_run(deps, platform, msg, triggers).await;
+}
+
+/// メッセージに付けられたリアクションを判定し、設定されたトリガーを実行する関数
+///
+/// 返信はリアクションが付いたメッセージに対して行います。
+/// 1つのメッセージには1回だけ応答し、本文などで応答済みの場合は何もしません。
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `platform` - リアクションや返信を送るプラットフォーム
+/// * `msg` - リアクションが付いたメッセージ（送信者はリアクションを付けたユーザー）
+/// * `emoji` - 付けられたリアクション
+pub async fn handle_reaction_add(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+    emoji: &Emoji,
+) {
+    if !should_handle(deps, msg) {
+        return;
+    }
+    let Some(_in_flight) = deps.shutdown.try_begin() else {
+        info!("Shutting down; ignoring reaction to message: {}", msg.id);
+        return;
+    };
+
+    let found = deps.matcher.find_reaction(emoji);
+    if found.is_empty() {
+        return;
+    }
+    for trigger in &found {
+        METRICS.triggers_matched.inc(trigger.name());
+    }
+    info!("Reaction {} added to message {}", emoji, msg.id);
+    let triggers = resolve_triggers(deps, msg, found);
+    claim_and_run(deps, platform, msg, triggers).await;
+}
+
+/// 重複応答を防いだうえで、トリガーを順に実行する関数
+async fn claim_and_run(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+    triggers: Vec<Trigger>,
+) {
+    let Some(&first) = triggers.first() else {
+        return;
+    };
+
+    // --- 同じメッセージへの重複応答を防ぐ ---
+    if !deps.replies.try_claim(msg.id, first) {
+        info!("Message {} has already been handled", msg.id);
+        return;
+    }
+
+    for trigger in triggers {
+        let replied = run_trigger(deps, platform, msg, trigger).await;
+        // --- ランキング用に返信したトリガーを記録（クールダウン中・送信失敗・DM は対象外） ---
+        if let (true, Some(guild_id)) = (replied, msg.guild_id) {
+            deps.stats
+                .record(guild_id, msg.author_id, trigger, deps.clock.now());
+        }
+    }
+}
+
+/// 編集されたメッセージを再判定し、応答を追加・取り消しする関数
+///
+/// - 編集で新たにトリガーが含まれた場合: 通常のメッセージと同様にリアクション・返信する
+/// - すでに応答済みの場合: 重複して応答しない
+/// - 編集でトリガーが消えた場合: 設定に応じてボットの返信を編集・削除する
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `platform` - リアクションや返信を送るプラットフォーム
+/// * `msg` - 編集後のメッセージ
+pub async fn handle_message_update(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+) {
+    if !should_handle(deps, msg) {
+        return;
+    }
+    let Some(_in_flight) = deps.shutdown.try_begin() else {
+        info!("Shutting down; ignoring edited message: {}", msg.id);
+        return;
+    };
+
+    // トリガーが消えたかどうかは検知結果そのもので判断する
+    // （許可/拒否リストや応答条件は、新たに応答するときだけ適用する）
+    let found = distinct_triggers(&deps.matcher.find(&msg.content, &msg.stickers));
+    match (found.is_empty(), deps.replies.get(msg.id)) {
+        (false, None) => {
+            for trigger in &found {
+                METRICS.triggers_matched.inc(trigger.name());
+            }
+            let triggers = resolve_triggers(deps, msg, found);
+            if !triggers.is_empty() {
+                info!("Edited message {} newly matched {:?}", msg.id, triggers);
+                claim_and_run(deps, platform, msg, triggers).await;
+            }
+        }
+        (true, Some(tracked)) => {
+            info!("Trigger was removed from edited message {}", msg.id);
+            handle_trigger_removed(deps, platform, msg, tracked.replies).await;
+        }
+        // 応答済みのままトリガーが残っている / もともと対象外
+        (false, Some(_)) | (true, None) => {}
+    }
+}
+
+/// 削除された元メッセージに対するボットの返信を削除する関数
+///
+/// 応答生成中（Ollama への問い合わせ中）であれば、問い合わせを取り消します。
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `platform` - 返信の削除に使うプラットフォーム
+/// * `message_id` - 削除されたメッセージID
+pub async fn handle_message_delete(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    message_id: u64,
+) {
+    if deps.replies.cancel_request(message_id) {
+        info!("Cancelled chat request for deleted message: {}", message_id);
+    }
+    let Some(tracked) = deps.replies.remove(message_id) else {
+        return;
+    };
+    for reply in tracked.replies {
+        match platform.delete(reply).await {
+            Ok(()) => info!("Deleted reply to deleted message: {}", message_id),
+            Err(e) => error!("{:?}", e),
+        }
+    }
+}
+
+/// 編集でトリガーが消えたときに、設定に応じてボットの返信を処理する関数
+async fn handle_trigger_removed(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+    replies: Vec<MessageRef>,
+) {
+    let action = deps.edits.on_trigger_removed;
+    if action == TriggerRemovedAction::Keep {
+        return;
+    }
+    // 追跡をやめ、再びトリガーが追加されたら新しく応答できるようにする
+    deps.replies.remove(msg.id);
+
+    for reply in replies {
+        let result = match action {
+            TriggerRemovedAction::Edit => platform.edit(reply, &deps.edits.removed_notice).await,
+            TriggerRemovedAction::Delete => platform.delete(reply).await,
+            TriggerRemovedAction::Keep => Ok(()),
+        };
+        if let Err(e) = result {
+            error!("{:?}", e);
+        }
+    }
+}
+
+/// ハンドラで処理すべきメッセージかどうかを判定する関数
+fn should_handle(deps: &HandlerDeps, msg: &IncomingMessage) -> bool {
+    // --- メッセージがボットからのものであれば無視 ---
+    if msg.author_bot {
+        return false;
+    }
+
+    // --- DM モードが無効であればダイレクトメッセージは無視 ---
+    if msg.guild_id.is_none() && !deps.dm.enabled {
+        info!("Received DM from user: {}", msg.author_name);
+        return false;
+    }
+    true
+}
+
+/// 一致した語句から、トリガーを重複なく出現順に返す関数
+fn distinct_triggers(matches: &[TriggerMatch]) -> Vec<Trigger> {
+    let mut triggers = Vec::new();
+    for m in matches {
+        if !triggers.contains(&m.trigger) {
+            triggers.push(m.trigger);
+        }
+    }
+    triggers
+}
+
+/// 検知したトリガーから、実行するトリガーを決める関数
+///
+/// 許可/拒否リスト（DM では対象外）と応答条件（確率・時間帯・曜日）を適用したうえで、
+/// `triggers.resolution` に従って選びます。
+/// - `first`: メッセージ内で最初に現れたトリガー
+/// - `priority`: `priority` が最も高いトリガー（同じなら侍 → ザウルスの順）
+/// - `all`: 一致したすべてのトリガー（現れた順）
+///
+/// DM でどのトリガーにも一致しないメッセージは、ザウルスとの会話として扱います
+/// （ザウルスの応答条件は適用します）。
+/// # 引数
+/// * `found` - 検知したトリガー（重複なし、現れた順）
+/// # 戻り値
+/// * `Vec<Trigger>` - 実行する順のトリガー（空なら応答しない）
+fn resolve_triggers(
+    deps: &HandlerDeps,
+    msg: &IncomingMessage,
+    found: Vec<Trigger>,
+) -> Vec<Trigger> {
+    let is_dm = msg.guild_id.is_none();
+    let found = if found.is_empty() && is_dm {
+        vec![Trigger::Zaurus]
+    } else {
+        found
+    };
+
+    // --- 許可/拒否リスト（DM はサーバー外なので対象外）と応答条件を確認 ---
+    let mut allowed: Vec<Trigger> = found
+        .into_iter()
+        .filter(|trigger| {
+            (is_dm || is_trigger_allowed(deps, *trigger, msg)) && is_trigger_open(deps, *trigger)
+        })
+        .collect();
+
+    match deps.triggers.resolution {
+        ResolutionPolicy::First => allowed.truncate(1),
+        ResolutionPolicy::Priority => {
+            // min_by_key は同じ値のうち最初の要素を返すので、同じ優先度なら Trigger::ALL の順
+            allowed = Trigger::ALL
+                .into_iter()
+                .filter(|trigger| allowed.contains(trigger))
+                .min_by_key(|trigger| std::cmp::Reverse(deps.triggers.get(*trigger).priority))
+                .into_iter()
+                .collect();
+        }
+        ResolutionPolicy::All => {}
+    }
+    allowed
+}
+
+/// トリガーに応じた処理を実行する関数
+/// # 戻り値
+/// * `bool` - 返信を送信したかどうか
+async fn run_trigger(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+    trigger: Trigger,
+) -> bool {
+    match trigger {
+        Trigger::Samurai => {
+            info!("Received '侍' from user: {}", msg.author_name);
+            handle_samurai(deps, platform, msg).await
+        }
+        Trigger::Zaurus => {
+            info!("Received 'ザウルス' from user: {}", msg.author_name);
+            handle_zaurus(deps, platform, msg).await
+        }
+    }
+}
+
+/// トリガーの許可/拒否リストを確認する関数（拒否された場合はログを出す）
+fn is_trigger_allowed(deps: &HandlerDeps, trigger: Trigger, msg: &IncomingMessage) -> bool {
+    let allowed = access::is_allowed(&deps.triggers.get(trigger).access, msg);
+    if !allowed {
+        info!(
+            "Trigger '{}' is not allowed in channel {} for user: {}",
+            trigger.name(),
+            msg.channel_id,
+            msg.author_name
+        );
+    }
+    allowed
+}
+
+/// トリガーの応答条件（確率・時間帯・曜日）を満たすかどうかを判定する関数
+fn is_trigger_open(deps: &HandlerDeps, trigger: Trigger) -> bool {
+    let open = gate::is_open(&deps.triggers.get(trigger).gate, deps.clock.now(), || {
+        deps.rng.with(|rng| rng.random())
+    });
+    if !open {
+        info!("Trigger '{}' is closed by its gate", trigger.name());
+    }
+    open
+}
+
+/// 侍を検知したときの処理（リアクション + ランダムな侍で返信）
+/// # 戻り値
+/// * `bool` - 返信を送信したかどうか（クールダウン中でリアクションのみの場合は `false`）
+async fn handle_samurai(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+) -> bool {
+    // --- クールダウンの確認 ---
+    // クールダウン中でも `react_while_cooling` が有効ならリアクションだけは行う
+    let should_reply = match deps.cooldowns.check(
+        Trigger::Samurai,
+        &deps.triggers.get(Trigger::Samurai).cooldown,
+        msg,
+    ) {
+        CooldownDecision::Allowed => true,
+        CooldownDecision::CoolingDown { react_only: true } => false,
+        CooldownDecision::CoolingDown { react_only: false } => {
+            info!(
+                "Samurai trigger is cooling down for user: {}",
+                msg.author_name
+            );
+            return false;
+        }
+    };
+
+    // --- メッセージの内容に応じてリアクションを実行 ---
+    // DM ではサーバーのカスタム絵文字が使えないため Unicode 絵文字を使う
+    let emoji = if msg.guild_id.is_none() {
+        Emoji::Unicode(deps.dm.reaction_emoji.clone())
+    } else {
+        Emoji::Custom(SAMURAI_EMOJI_NAME.to_string())
+    };
+    if let Err(e) = platform.react(msg, &emoji).await {
+        error!("Error: {:?}", e);
+    }
+    if !should_reply {
+        info!(
+            "Samurai trigger is cooling down for user: {} (reaction only)",
+            msg.author_name
+        );
+        return false;
+    }
+
+    // --- ランダムな侍を過去データから取得してリプライ ---
+    let entry = match deps
+        .rng
+        .with(|rng| table::get_samurai_name(&deps.samurai_entries, rng))
+    {
+        Ok(Some(entry)) => entry,
+        Ok(None) => {
+            error!("Samurai not found");
+            return false;
+        }
+        Err(e) => {
+            error!("Error: {}", e);
+            return false;
+        }
+    };
+    info!("Samurai name: {}", entry.name);
+
+    let result = match deps.samurai_reply.style {
+        ReplyStyle::Embed => {
+            let buttons = if deps.buttons.enabled {
+                buttons::samurai_buttons(entry.id, msg.author_id, deps.clock.now())
+            } else {
+                Vec::new()
+            };
+            let embed = samurai_embed(&deps.samurai_reply, &entry, !buttons.is_empty());
+            platform.reply_embed(msg, &embed, &buttons).await
+        }
+        ReplyStyle::Text => {
+            let text = samurai_text(deps, msg, &entry);
+            platform.reply(msg, &text).await
+        }
+    };
+    match result {
+        Ok(reply) => track_reply(deps, platform, msg, reply).await,
+        Err(e) => {
+            error!("{:?}", e);
+            return false;
+        }
+    }
+    info!("Replied to message: {}", msg.id);
+
+    // --- 引いた侍をコレクションに記録 ---
+    deps.collection.record_draw(msg.author_id, entry.id);
+    true
+}
+
+/// 侍を返信用の埋め込みに変換する関数
+/// # 引数
+/// * `config` - 侍の返信の表示設定
+/// * `entry` - 侍データ
+/// * `preview` - 説明を先頭だけに省略するかどうか（「詳細」ボタンで全文を表示する場合）
+/// # 戻り値
+/// * `Embed` - タイトルに名前、本文に説明、フッターに ID（とレアリティ）を表示する埋め込み
+pub fn samurai_embed(config: &SamuraiReplyConfig, entry: &SamuraiEntry, preview: bool) -> Embed {
+    let footer = match &entry.rarity {
+        Some(rarity) => format!("ID: {} ・ {}", entry.id, rarity),
+        None => format!("ID: {}", entry.id),
+    };
+    let description = if preview && entry.description.chars().count() > DESCRIPTION_PREVIEW_CHARS {
+        let head: String = entry
+            .description
+            .chars()
+            .take(DESCRIPTION_PREVIEW_CHARS)
+            .collect();
+        format!("{}…", head)
+    } else {
+        entry.description.clone()
+    };
+    Embed {
+        title: entry.name.clone(),
+        description,
+        color: config.color_for(entry.rarity.as_deref()),
+        footer: Some(footer),
+        thumbnail_url: entry.thumbnail_url.clone(),
+        image_url: entry.image_url.clone(),
+    }
+}
+
+/// 侍の返信テキストをテンプレートから作成する関数
+///
+/// 値に含まれるメンションは無効化し、送信者名は Markdown もエスケープします。
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `msg` - 返信対象のメッセージ
+/// * `entry` - 侍データ
+fn samurai_text(deps: &HandlerDeps, msg: &IncomingMessage, entry: &SamuraiEntry) -> String {
+    let id = entry.id.to_string();
+    let name = template::escape_mentions(&entry.name);
+    let description = template::escape_mentions(&entry.description);
+    let rarity = template::escape_mentions(entry.rarity.as_deref().unwrap_or(""));
+    let author = author_var(msg);
+    template::render(
+        deps.templates.get(Trigger::Samurai, msg.guild_id),
+        &[
+            ("id", &id),
+            ("name", &name),
+            ("description", &description),
+            ("rarity", &rarity),
+            ("author", &author),
+        ],
+    )
+}
+
+/// ザウルスの返信テキストをテンプレートから作成する関数
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `msg` - 返信対象のメッセージ
+/// * `reply` - チャットボットの応答
+fn zaurus_text(deps: &HandlerDeps, msg: &IncomingMessage, reply: &str) -> String {
+    let reply = template::escape_mentions(reply);
+    let author = author_var(msg);
+    template::render(
+        deps.templates.get(Trigger::Zaurus, msg.guild_id),
+        &[("reply", &reply), ("author", &author)],
+    )
+}
+
+/// テンプレートの `{author}` に埋め込む送信者名（メンション・Markdown をエスケープ済み）
+fn author_var(msg: &IncomingMessage) -> String {
+    template::escape_markdown(&template::escape_mentions(&msg.author_name))
+}
+
+/// ザウルスを検知したときの処理（チャットボットの応答で返信）
+/// # 戻り値
+/// * `bool` - 返信を送信したかどうか
+async fn handle_zaurus(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+) -> bool {
+    // --- クールダウンの確認（ザウルスはリアクションを行わないため、クールダウン中は何もしない） ---
+    let decision = deps.cooldowns.check(
+        Trigger::Zaurus,
+        &deps.triggers.get(Trigger::Zaurus).cooldown,
+        msg,
+    );
+    if decision != CooldownDecision::Allowed {
+        info!(
+            "Zaurus trigger is cooling down for user: {}",
+            msg.author_name
+        );
+        return false;
+    }
+
+    // --- 応答生成中であることを表示 ---
+    if let Err(e) = platform.typing(msg.channel_id).await {
+        error!("{:?}", e);
+    }
+
+    // --- メッセージの内容に応じてチャットボットで応答 ---
+    // DM ではユーザーごとの会話履歴を含めて問い合わせる
+    // 元メッセージが削除されたら問い合わせを取り消す
+    let is_dm = msg.guild_id.is_none();
+    let cancelled = deps.replies.begin_request(msg.id);
+    let chat = async {
+        if is_dm {
+            let history = deps.memory.history(msg.author_id);
+            deps.chat.chat_with_history(&history, &msg.content).await
+        } else {
+            deps.chat.chat_once(&msg.content).await
+        }
+    };
+    let result = tokio::select! {
+        result = chat => Some(result),
+        _ = cancelled.cancelled() => None,
+    };
+    deps.replies.finish_request(msg.id);
+    let Some(result) = result else {
+        info!("Chat request was cancelled for message: {}", msg.id);
+        return false;
+    };
+    let reply = match result {
+        Ok(text) => {
+            deps.health.record_ollama_success();
+            if is_dm {
+                deps.memory.record(msg.author_id, &msg.content, &text);
+            }
+            text
+        }
+        Err(e) => {
+            deps.health.record_ollama_failure(&format!("{:#}", e));
+            error!("Error chatting with Ollama: {:?}", e);
+            return false;
+        }
+    };
+
+    // --- メッセージにリプライ ---
+    let reply = zaurus_text(deps, msg, &reply);
+    match platform.reply(msg, &reply).await {
+        Ok(sent) => track_reply(deps, platform, msg, sent).await,
+        Err(e) => {
+            error!("{:?}", e);
+            return false;
+        }
+    }
+    info!("Replied to message: {}", msg.id);
+    true
+}
+
+/// 送信した返信を記録する関数
+///
+/// 返信の送信中に元メッセージが削除されていた場合は、取り残された返信を削除します。
+async fn track_reply(
+    deps: &HandlerDeps,
+    platform: &dyn ChatPlatform,
+    msg: &IncomingMessage,
+    reply: MessageRef,
+) {
+    if deps.replies.add_reply(msg.id, reply) {
+        return;
+    }
+    info!("Source message {} was deleted while replying", msg.id);
+    if let Err(e) = platform.delete(reply).await {
+        error!("{:?}", e);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::chat::Message;
+    use crate::clock::FixedClock;
+    use crate::config::{BucketConfig, GuildTemplates};
+    use crate::platform::{RecordedAction, RecordingPlatform};
+    use crate::stats::Period;
+    use anyhow::{Result, anyhow};
+    use serenity::async_trait;
+
+    /// テスト用の依存が返す現在時刻（2024-01-03（水）12:00 JST）
+    pub(crate) const NOW: u64 = 1_704_250_800;
+
+    /// 固定の応答を返すテスト用チャットバックエンド
+    pub(crate) struct FixedChat(pub Option<&'static str>);
+
+    #[async_trait]
+    impl ChatBackend for FixedChat {
+        async fn chat_with_history(
+            &self,
+            _history: &[Message],
+            _user_input: &str,
+        ) -> Result<String> {
+            self.0
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("backend unavailable"))
+        }
+    }
+
+    /// 受け取った履歴の件数を応答に含めるテスト用チャットバックエンド
+    struct HistoryCountChat;
+
+    #[async_trait]
+    impl ChatBackend for HistoryCountChat {
+        async fn chat_with_history(&self, history: &[Message], user_input: &str) -> Result<String> {
+            Ok(format!("{}:{}", history.len(), user_input))
+        }
+    }
+
+    /// 応答を返さないテスト用チャットバックエンド（取り消しの確認用）
+    struct PendingChat;
+
+    #[async_trait]
+    impl ChatBackend for PendingChat {
+        async fn chat_with_history(
+            &self,
+            _history: &[Message],
+            _user_input: &str,
+        ) -> Result<String> {
+            std::future::pending().await
+        }
+    }
+
+    pub(crate) fn deps(chat: impl ChatBackend + 'static) -> HandlerDeps {
+        HandlerDeps {
+            chat: Arc::new(chat),
+            samurai_entries: Arc::new(vec![SamuraiEntry {
+                name: "テスト侍".to_string(),
+                description: "テストテストテスト".to_string(),
+                ..Default::default()
+            }]),
+            shutdown: Arc::new(Shutdown::new()),
+            health: Arc::new(HealthState::new(Ok(1))),
+            triggers: TriggersConfig::default(),
+            matcher: TriggerMatcher::new(&TriggersConfig::default()).unwrap(),
+            cooldowns: Cooldowns::new(),
+            dm: DmConfig::default(),
+            memory: ConversationMemory::new(2),
+            edits: EditsConfig::default(),
+            replies: ReplyTracker::new(),
+            samurai_reply: SamuraiReplyConfig::default(),
+            templates: TemplatesConfig::default(),
+            buttons: ButtonsConfig::default(),
+            rerolls: Cooldowns::new(),
+            collection: Collection::new(),
+            stats: TriggerStats::new(),
+            daily: DailyConfig::default(),
+            clock: Arc::new(FixedClock(NOW)),
+            rng: SharedRng::seeded(0),
+        }
+    }
+
+    fn guild_message(content: &str) -> IncomingMessage {
+        IncomingMessage {
+            id: 10,
+            channel_id: 20,
+            guild_id: Some(30),
+            author_id: 40,
+            author_name: "tester".to_string(),
+            author_bot: false,
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn samurai_message_reacts_and_replies() {
+        let platform = RecordingPlatform::new();
+        handle_message(
+            &deps(FixedChat(Some("unused"))),
+            &platform,
+            &guild_message("ゲームしたい侍"),
+        )
+        .await;
+
+        let actions = platform.actions();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(
+            actions[0],
+            RecordedAction::React {
+                message_id: 10,
+                emoji: Emoji::Custom("kasu".to_string()),
+            }
+        );
+        match &actions[1] {
+            RecordedAction::ReplyEmbed {
+                message_id,
+                embed,
+                buttons,
+                ..
+            } => {
+                assert_eq!(*message_id, 10);
+                assert_eq!(
+                    *embed,
+                    Embed {
+                        title: "テスト侍".to_string(),
+                        description: "テストテストテスト".to_string(),
+                        color: SamuraiReplyConfig::default().color,
+                        footer: Some("ID: 0".to_string()),
+                        ..Default::default()
+                    }
+                );
+                let labels: Vec<&str> = buttons.iter().map(|b| b.label.as_str()).collect();
+                assert_eq!(labels, vec!["もう一回", "詳細", "お気に入り"]);
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn samurai_draws_are_recorded_in_collection() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        assert_eq!(deps.collection.get(40).seen.get(&0), Some(&1));
+
+        // クールダウンなどで返信しなかった場合は記録しない
+        assert!(deps.collection.get(41).seen.is_empty());
+    }
+
+    #[tokio::test]
+    async fn guild_triggers_are_recorded_for_leaderboard() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(Some("返答")));
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        let zaurus = IncomingMessage {
+            id: 11,
+            ..guild_message("ザウルス")
+        };
+        handle_message(&deps, &platform, &zaurus).await;
+        let dm = IncomingMessage {
+            id: 12,
+            guild_id: None,
+            ..guild_message("侍")
+        };
+        handle_message(&deps, &platform, &dm).await;
+
+        let ranking = deps.stats.leaderboard(30, Period::AllTime, None, NOW, 10);
+        assert_eq!(ranking, vec![(40, 2)]);
+    }
+
+    #[tokio::test]
+    async fn buttons_can_be_disabled() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("unused")));
+        deps.buttons.enabled = false;
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        assert!(matches!(
+            &platform.actions()[1],
+            RecordedAction::ReplyEmbed { buttons, .. } if buttons.is_empty()
+        ));
+    }
+
+    #[tokio::test]
+    async fn samurai_reply_can_use_text_style() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("unused")));
+        deps.samurai_reply.style = ReplyStyle::Text;
+        handle_message(&deps, &platform, &guild_message("ゲームしたい侍")).await;
+
+        match &platform.actions()[1] {
+            RecordedAction::Reply {
+                message_id,
+                content,
+                ..
+            } => {
+                assert_eq!(*message_id, 10);
+                assert_eq!(content, "0: テスト侍\nテストテストテスト");
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn replies_use_per_guild_templates_with_escaping() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("@everyone がおー")));
+        deps.samurai_reply.style = ReplyStyle::Text;
+        deps.templates.samurai = Some("{author}さん、今日の侍は {name} です！".to_string());
+        deps.templates.guilds.insert(
+            "30".to_string(),
+            GuildTemplates {
+                zaurus: Some("🦖 {reply}".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let mut samurai = guild_message("侍");
+        samurai.author_name = "__user__".to_string();
+        handle_message(&deps, &platform, &samurai).await;
+        assert!(matches!(
+            &platform.take_actions()[1],
+            RecordedAction::Reply { content, .. }
+                if content == r"\_\_user\_\_さん、今日の侍は テスト侍 です！"
+        ));
+
+        let mut zaurus = guild_message("ザウルス");
+        zaurus.id = 11;
+        handle_message(&deps, &platform, &zaurus).await;
+        assert!(matches!(
+            &platform.take_actions()[1],
+            RecordedAction::Reply { content, .. } if content == "🦖 @\u{200B}everyone がおー"
+        ));
+    }
+
+    #[test]
+    fn samurai_embed_previews_long_descriptions() {
+        let entry = SamuraiEntry {
+            name: "長い侍".to_string(),
+            description: "あ".repeat(DESCRIPTION_PREVIEW_CHARS + 1),
+            ..Default::default()
+        };
+        let config = SamuraiReplyConfig::default();
+        let preview = samurai_embed(&config, &entry, true);
+        assert_eq!(
+            preview.description,
+            format!("{}…", "あ".repeat(DESCRIPTION_PREVIEW_CHARS))
+        );
+        assert_eq!(
+            samurai_embed(&config, &entry, false).description,
+            entry.description
+        );
+    }
+
+    #[test]
+    fn samurai_embed_uses_rarity_and_images() {
+        let entry = SamuraiEntry {
+            id: 3,
+            name: "レア侍".to_string(),
+            description: "説明".to_string(),
+            rarity: Some("SSR".to_string()),
+            thumbnail_url: Some("https://example.com/t.png".to_string()),
+            image_url: Some("https://example.com/i.png".to_string()),
+        };
+        let config = SamuraiReplyConfig::default();
+        let embed = samurai_embed(&config, &entry, false);
+        assert_eq!(embed.footer.as_deref(), Some("ID: 3 ・ SSR"));
+        assert_eq!(embed.description, "説明");
+        assert_eq!(embed.color, config.color_for(Some("SSR")));
+        assert_ne!(embed.color, config.color);
+        assert_eq!(
+            embed.thumbnail_url.as_deref(),
+            Some("https://example.com/t.png")
+        );
+        assert_eq!(
+            embed.image_url.as_deref(),
+            Some("https://example.com/i.png")
+        );
+    }
+
+    #[tokio::test]
+    async fn zaurus_message_replies_with_chat_response() {
+        let platform = RecordingPlatform::new();
+        handle_message(
+            &deps(FixedChat(Some("がおー"))),
+            &platform,
+            &guild_message("うおーザウルス"),
+        )
+        .await;
+
+        let actions = platform.actions();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0], RecordedAction::Typing { channel_id: 20 });
+        assert!(matches!(
+            &actions[1],
+            RecordedAction::Reply { message_id: 10, content, .. } if content == "がおー"
+        ));
+    }
+
+    #[tokio::test]
+    async fn samurai_takes_precedence_over_zaurus() {
+        let platform = RecordingPlatform::new();
+        handle_message(
+            &deps(FixedChat(Some("がおー"))),
+            &platform,
+            &guild_message("侍ザウルス"),
+        )
+        .await;
+
+        let actions = platform.actions();
+        assert!(matches!(actions[0], RecordedAction::React { .. }));
+        assert!(
+            !actions
+                .iter()
+                .any(|a| matches!(a, RecordedAction::Typing { .. }))
+        );
+    }
+
+    #[tokio::test]
+    async fn resolution_policy_picks_triggers() {
+        // (ポリシー, ザウルスの優先度, 実行されるトリガー（Trigger::ALL の順）)
+        let cases = [
+            (ResolutionPolicy::First, 0, vec![Trigger::Zaurus]),
+            (ResolutionPolicy::Priority, 0, vec![Trigger::Samurai]),
+            (ResolutionPolicy::Priority, 10, vec![Trigger::Zaurus]),
+            (
+                ResolutionPolicy::All,
+                0,
+                vec![Trigger::Samurai, Trigger::Zaurus],
+            ),
+        ];
+        for (policy, priority, expected) in cases {
+            let platform = RecordingPlatform::new();
+            let mut deps = deps(FixedChat(Some("がおー")));
+            deps.triggers.resolution = policy;
+            deps.triggers.zaurus.priority = priority;
+            handle_message(&deps, &platform, &guild_message("ザウルスと侍")).await;
+
+            let ranking = |trigger| {
+                deps.stats
+                    .leaderboard(30, Period::AllTime, Some(trigger), NOW, 10)
+            };
+            let ran: Vec<Trigger> = Trigger::ALL
+                .into_iter()
+                .filter(|trigger| !ranking(*trigger).is_empty())
+                .collect();
+            assert_eq!(ran, expected, "{:?}", policy);
+
+            let replies = platform
+                .actions()
+                .into_iter()
+                .filter(|a| {
+                    matches!(
+                        a,
+                        RecordedAction::Reply { .. } | RecordedAction::ReplyEmbed { .. }
+                    )
+                })
+                .count();
+            assert_eq!(replies, expected.len(), "{:?}", policy);
+        }
+    }
+
+    #[tokio::test]
+    async fn deleting_source_deletes_every_reply() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("がおー")));
+        deps.triggers.resolution = ResolutionPolicy::All;
+        handle_message(&deps, &platform, &guild_message("侍ザウルス")).await;
+        let replies: Vec<MessageRef> = platform
+            .take_actions()
+            .into_iter()
+            .filter_map(|a| match a {
+                RecordedAction::Reply { reply, .. } | RecordedAction::ReplyEmbed { reply, .. } => {
+                    Some(reply)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(replies.len(), 2);
+
+        handle_message_delete(&deps, &platform, 10).await;
+        let deleted: Vec<RecordedAction> = replies
+            .into_iter()
+            .map(|target| RecordedAction::Delete { target })
+            .collect();
+        assert_eq!(platform.take_actions(), deleted);
+    }
+
+    #[tokio::test]
+    async fn stickers_and_reactions_trigger_samurai() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("unused")));
+        deps.triggers.samurai.stickers = vec!["侍スタンプ".to_string()];
+        deps.triggers.samurai.reactions = vec!["kasu".to_string()];
+        deps.matcher = TriggerMatcher::new(&deps.triggers).unwrap();
+
+        // 本文のないスタンプだけのメッセージ
+        let sticker = IncomingMessage {
+            stickers: vec!["侍スタンプ".to_string()],
+            ..guild_message("")
+        };
+        handle_message(&deps, &platform, &sticker).await;
+        assert!(matches!(
+            &platform.take_actions()[..],
+            [
+                RecordedAction::React { message_id: 10, .. },
+                RecordedAction::ReplyEmbed { message_id: 10, .. }
+            ]
+        ));
+
+        // リアクションを付けたメッセージに返信する（送信者はリアクションを付けたユーザー）
+        let kasu = Emoji::Custom("kasu".to_string());
+        let reacted = IncomingMessage {
+            id: 11,
+            author_id: 41,
+            ..guild_message("普通の文章")
+        };
+        handle_reaction_add(&deps, &platform, &reacted, &kasu).await;
+        assert!(matches!(
+            &platform.take_actions()[..],
+            [
+                RecordedAction::React { message_id: 11, .. },
+                RecordedAction::ReplyEmbed { message_id: 11, .. }
+            ]
+        ));
+        let ranking = deps.stats.leaderboard(30, Period::AllTime, None, NOW, 10);
+        assert_eq!(ranking, vec![(40, 1), (41, 1)]);
+
+        // 応答済みのメッセージ・ボットのリアクション・設定にないリアクションでは何もしない
+        handle_reaction_add(&deps, &platform, &reacted, &kasu).await;
+        let by_bot = IncomingMessage {
+            id: 12,
+            author_bot: true,
+            ..guild_message("普通の文章")
+        };
+        handle_reaction_add(&deps, &platform, &by_bot, &kasu).await;
+        let other = IncomingMessage {
+            id: 13,
+            ..guild_message("普通の文章")
+        };
+        handle_reaction_add(&deps, &platform, &other, &Emoji::Unicode("👍".to_string())).await;
+        assert!(platform.actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn gates_use_injected_clock_and_rng() {
+        /// 2024-01-03（水）12:00 JST
+        const WEDNESDAY_NOON: u64 = 1_704_250_800;
+
+        // 水曜日の昼は休止時間帯なので、リアクションも返信もしない
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("unused")));
+        deps.clock = Arc::new(FixedClock(WEDNESDAY_NOON));
+        deps.triggers.samurai.gate.quiet_hours =
+            vec![crate::config::TimeWindow::try_from("11:00-14:00".to_string()).unwrap()];
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        assert!(platform.take_actions().is_empty());
+
+        // 時間帯の外になれば応答し、その時刻でランキングに記録する
+        deps.clock = Arc::new(FixedClock(WEDNESDAY_NOON + 3 * 60 * 60));
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        assert_eq!(platform.take_actions().len(), 2);
+        let ranking = deps
+            .stats
+            .leaderboard(30, Period::Daily, None, WEDNESDAY_NOON, 10);
+        assert_eq!(ranking, vec![(40, 1)]);
+
+        // 同じシードなら、確率による応答の有無も同じになる
+        let replied = |seed| async move {
+            let platform = RecordingPlatform::new();
+            let mut deps = super::tests::deps(FixedChat(Some("unused")));
+            deps.rng = SharedRng::seeded(seed);
+            deps.triggers.samurai.gate.probability = 0.5;
+            let mut replied = Vec::new();
+            for id in 0..20 {
+                let msg = IncomingMessage {
+                    id,
+                    ..guild_message("侍")
+                };
+                handle_message(&deps, &platform, &msg).await;
+                replied.push(!platform.take_actions().is_empty());
+            }
+            replied
+        };
+        let first = replied(7).await;
+        assert_eq!(first, replied(7).await);
+        assert!(first.contains(&true) && first.contains(&false));
+    }
+
+    #[tokio::test]
+    async fn chat_failure_sends_no_reply() {
+        let platform = RecordingPlatform::new();
+        handle_message(
+            &deps(FixedChat(None)),
+            &platform,
+            &guild_message("ザウルス"),
+        )
+        .await;
+
+        assert_eq!(
+            platform.actions(),
+            vec![RecordedAction::Typing { channel_id: 20 }]
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_result_is_recorded_in_health() {
+        let platform = RecordingPlatform::new();
+        let failing = deps(FixedChat(None));
+        handle_message(&failing, &platform, &guild_message("ザウルス")).await;
+        assert_eq!(
+            failing.health.details()["ollama"]["last_error"],
+            "backend unavailable"
+        );
+
+        let working = deps(FixedChat(Some("がおー")));
+        handle_message(&working, &platform, &guild_message("ザウルス")).await;
+        assert!(working.health.details()["ollama"]["last_success_unix"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn samurai_cooldown_can_keep_reacting() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("がおー")));
+        deps.triggers.samurai.cooldown.user = Some(BucketConfig {
+            capacity: 1,
+            refill_secs: 3600.0,
+        });
+        deps.triggers.samurai.cooldown.react_while_cooling = true;
+
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        assert_eq!(platform.take_actions().len(), 2);
+
+        // クールダウン中はリアクションのみ
+        let mut second = guild_message("侍");
+        second.id = 11;
+        handle_message(&deps, &platform, &second).await;
+        let actions = platform.take_actions();
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(actions[0], RecordedAction::React { .. }));
+
+        // react_while_cooling が偽なら何もしない
+        deps.triggers.samurai.cooldown.react_while_cooling = false;
+        let mut third = guild_message("侍");
+        third.id = 12;
+        handle_message(&deps, &platform, &third).await;
+        assert!(platform.take_actions().is_empty());
+
+        // ランキングには返信した1回だけを記録する
+        let ranking = deps.stats.leaderboard(30, Period::AllTime, None, NOW, 10);
+        assert_eq!(ranking, vec![(40, 1)]);
+    }
+
+    #[tokio::test]
+    async fn zaurus_cooldown_skips_chat() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("がおー")));
+        deps.triggers.zaurus.cooldown.channel = Some(BucketConfig {
+            capacity: 1,
+            refill_secs: 3600.0,
+        });
+
+        handle_message(&deps, &platform, &guild_message("ザウルス")).await;
+        assert_eq!(platform.take_actions().len(), 2);
+
+        let mut second = guild_message("ザウルス");
+        second.id = 11;
+        handle_message(&deps, &platform, &second).await;
+        assert!(platform.take_actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn access_lists_are_enforced_per_trigger() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("がおー")));
+        deps.triggers.samurai.access.allow_channels = vec![99];
+        deps.triggers.zaurus.access.allow_roles = vec![7];
+
+        // 侍は許可されていないチャンネルなので、ザウルスの判定に進む（ロールがないので何もしない）
+        handle_message(&deps, &platform, &guild_message("侍ザウルス")).await;
+        assert!(platform.take_actions().is_empty());
+
+        // ロールを持っていればザウルスが応答する
+        let mut with_role = guild_message("侍ザウルス");
+        with_role.id = 11;
+        with_role.role_ids = vec![7];
+        handle_message(&deps, &platform, &with_role).await;
+        let actions = platform.take_actions();
+        assert_eq!(actions[0], RecordedAction::Typing { channel_id: 20 });
+
+        // 許可されたチャンネルなら侍が応答する
+        let mut in_random = guild_message("侍");
+        in_random.id = 12;
+        in_random.channel_id = 99;
+        handle_message(&deps, &platform, &in_random).await;
+        assert!(matches!(
+            platform.take_actions()[0],
+            RecordedAction::React { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn dm_mode_uses_unicode_reaction_and_memory() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(HistoryCountChat);
+        deps.dm.enabled = true;
+        deps.triggers.samurai.access.allow_channels = vec![99];
+
+        let mut dm = guild_message("今日の侍");
+        dm.guild_id = None;
+        handle_message(&deps, &platform, &dm).await;
+        let actions = platform.take_actions();
+        assert_eq!(
+            actions[0],
+            RecordedAction::React {
+                message_id: 10,
+                emoji: Emoji::Unicode("⚔️".to_string()),
+            }
+        );
+        assert!(matches!(&actions[1], RecordedAction::ReplyEmbed { .. }));
+
+        // トリガー語がなくてもザウルスと会話でき、履歴が積み上がる
+        for (id, input, expected) in [
+            (11, "こんにちは", "0:こんにちは"),
+            (12, "元気？", "2:元気？"),
+        ] {
+            let mut dm = guild_message(input);
+            dm.id = id;
+            dm.guild_id = None;
+            handle_message(&deps, &platform, &dm).await;
+            let actions = platform.take_actions();
+            assert!(matches!(
+                &actions[1],
+                RecordedAction::Reply { content, .. } if content == expected
+            ));
+        }
+
+        // サーバー内のザウルスは履歴を使わない
+        let mut in_guild = guild_message("ザウルス");
+        in_guild.id = 13;
+        handle_message(&deps, &platform, &in_guild).await;
+        assert!(matches!(
+            &platform.take_actions()[1],
+            RecordedAction::Reply { content, .. } if content == "0:ザウルス"
+        ));
+    }
+
+    #[tokio::test]
+    async fn gates_apply_in_dms() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("がおー")));
+        deps.dm.enabled = true;
+        deps.triggers.samurai.gate.probability = 0.0;
+        deps.triggers.zaurus.gate.quiet_hours =
+            vec![crate::config::TimeWindow::try_from("11:00-14:00".to_string()).unwrap()];
+
+        // 侍は確率 0、ザウルスとの会話は休止時間帯なので、どちらも応答しない
+        for (id, content) in [(10, "侍"), (11, "こんにちは")] {
+            let dm = IncomingMessage {
+                id,
+                guild_id: None,
+                ..guild_message(content)
+            };
+            handle_message(&deps, &platform, &dm).await;
+        }
+        assert!(platform.take_actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deleting_source_deletes_reply() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(Some("がおー")));
+
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        let reply = match &platform.take_actions()[1] {
+            RecordedAction::ReplyEmbed { reply, .. } => *reply,
+            other => panic!("unexpected action: {:?}", other),
+        };
+
+        handle_message_delete(&deps, &platform, 10).await;
+        assert_eq!(
+            platform.take_actions(),
+            vec![RecordedAction::Delete { target: reply }]
+        );
+
+        // 追跡していないメッセージの削除では何もしない
+        handle_message_delete(&deps, &platform, 10).await;
+        handle_message_delete(&deps, &platform, 99).await;
+        assert!(platform.take_actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deleting_source_cancels_chat_request() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(PendingChat);
+        let msg = guild_message("ザウルス");
+
+        tokio::join!(handle_message(&deps, &platform, &msg), async {
+            // 問い合わせが始まってから削除する
+            tokio::task::yield_now().await;
+            handle_message_delete(&deps, &platform, msg.id).await;
+        });
+
+        assert_eq!(
+            platform.actions(),
+            vec![RecordedAction::Typing { channel_id: 20 }]
+        );
+        assert!(deps.replies.get(msg.id).is_none());
+    }
+
+    #[tokio::test]
+    async fn same_message_is_handled_once() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(Some("がおー")));
+
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        assert_eq!(platform.actions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn edit_adding_trigger_reacts_and_replies_once() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(Some("がおー")));
+
+        handle_message(&deps, &platform, &guild_message("ゲームしたい")).await;
+        assert!(platform.take_actions().is_empty());
+
+        handle_message_update(&deps, &platform, &guild_message("ゲームしたい侍")).await;
+        let actions = platform.take_actions();
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(actions[0], RecordedAction::React { .. }));
+
+        // 再編集してもトリガーが残っていれば重複して応答しない
+        handle_message_update(&deps, &platform, &guild_message("ゲームしたい侍！")).await;
+        assert!(platform.take_actions().is_empty());
+
+        // 編集で追加されたトリガーもランキングに1回だけ記録する
+        let ranking = deps
+            .stats
+            .leaderboard(30, Period::AllTime, Some(Trigger::Samurai), NOW, 10);
+        assert_eq!(ranking, vec![(40, 1)]);
+    }
+
+    #[tokio::test]
+    async fn edit_keeping_trigger_ignores_gates() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(Some("がおー")));
+        deps.edits.on_trigger_removed = TriggerRemovedAction::Delete;
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+        assert_eq!(platform.take_actions().len(), 2);
+
+        // 応答条件で閉じていても、トリガーが残っていれば返信を消さない
+        deps.triggers.samurai.gate.probability = 0.0;
+        handle_message_update(&deps, &platform, &guild_message("侍です")).await;
+        assert!(!platform.take_actions().iter().any(|action| matches!(
+            action,
+            RecordedAction::Delete { .. } | RecordedAction::Edit { .. }
+        )));
+        assert!(deps.replies.get(10).is_some());
+    }
+
+    #[tokio::test]
+    async fn edit_removing_trigger_follows_config() {
+        for (action, expect_edit, expect_delete) in [
+            (TriggerRemovedAction::Keep, false, false),
+            (TriggerRemovedAction::Edit, true, false),
+            (TriggerRemovedAction::Delete, false, true),
+        ] {
+            let platform = RecordingPlatform::new();
+            let mut deps = deps(FixedChat(Some("がおー")));
+            deps.edits.on_trigger_removed = action;
+
+            handle_message(&deps, &platform, &guild_message("侍")).await;
+            let reply = match &platform.take_actions()[1] {
+                RecordedAction::ReplyEmbed { reply, .. } => *reply,
+                other => panic!("unexpected action: {:?}", other),
+            };
+
+            handle_message_update(&deps, &platform, &guild_message("普通の文章")).await;
+            let actions = platform.take_actions();
+            assert_eq!(
+                actions.contains(&RecordedAction::Edit {
+                    target: reply,
+                    content: deps.edits.removed_notice.clone(),
+                }),
+                expect_edit
+            );
+            assert_eq!(
+                actions.contains(&RecordedAction::Delete { target: reply }),
+                expect_delete
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn ignores_triggers_while_shutting_down() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(Some("がおー")));
+        deps.shutdown.begin();
+
+        handle_message(&deps, &platform, &guild_message("侍")).await;
+
+        assert!(platform.actions().is_empty());
+        assert_eq!(deps.shutdown.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn ignores_bots_dms_and_plain_messages() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(Some("がおー")));
+
+        let mut bot = guild_message("侍");
+        bot.author_bot = true;
+        handle_message(&deps, &platform, &bot).await;
+
+        let mut dm = guild_message("侍");
+        dm.guild_id = None;
+        handle_message(&deps, &platform, &dm).await;
+
+        handle_message(&deps, &platform, &guild_message("普通の文章")).await;
+
+        assert!(platform.actions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ignores_triggers_in_code_quotes_and_urls() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(Some("がおー")));
+        for (id, content) in [
+            (10, "`侍`"),
+            (11, "> ザウルス\nだよね"),
+            (12, "https://example.com/侍"),
+            (13, "||侍||"),
+        ] {
+            let msg = IncomingMessage {
+                id,
+                ..guild_message(content)
+            };
+            handle_message(&deps, &platform, &msg).await;
+        }
+        assert!(platform.actions().is_empty());
+    }
+}
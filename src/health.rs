@@ -0,0 +1,141 @@
+use serde_json::{Value, json};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// ボットの稼働状態を保持する構造体
+///
+/// `/healthz`・`/readyz` エンドポイントで公開する情報を集約します。
+#[derive(Debug)]
+pub struct HealthState {
+    gateway_connected: AtomicBool,
+    samurai: Result<usize, String>,
+    ollama: Mutex<OllamaStatus>,
+}
+
+/// Ollama への直近の問い合わせ結果
+#[derive(Debug, Default, Clone)]
+struct OllamaStatus {
+    last_success: Option<SystemTime>,
+    last_failure: Option<SystemTime>,
+    last_error: Option<String>,
+}
+
+impl HealthState {
+    /// 新しい HealthState を作成する関数
+    /// # 引数
+    /// * `samurai` - 侍データの読み込み結果（成功時は件数、失敗時はエラーメッセージ）
+    pub fn new(samurai: Result<usize, String>) -> Self {
+        Self {
+            gateway_connected: AtomicBool::new(false),
+            samurai,
+            ollama: Mutex::new(OllamaStatus::default()),
+        }
+    }
+
+    /// ゲートウェイの接続状態を更新する関数
+    /// # 引数
+    /// * `connected` - 接続中であれば `true`
+    pub fn set_gateway_connected(&self, connected: bool) {
+        self.gateway_connected.store(connected, Ordering::SeqCst);
+    }
+
+    /// Ollama への問い合わせ成功を記録する関数
+    pub fn record_ollama_success(&self) {
+        if let Ok(mut status) = self.ollama.lock() {
+            status.last_success = Some(SystemTime::now());
+        }
+    }
+
+    /// Ollama への問い合わせ失敗を記録する関数
+    /// # 引数
+    /// * `error` - エラー内容
+    pub fn record_ollama_failure(&self, error: &str) {
+        if let Ok(mut status) = self.ollama.lock() {
+            status.last_failure = Some(SystemTime::now());
+            status.last_error = Some(error.to_string());
+        }
+    }
+
+    /// 応答可能な状態かどうかを返す関数
+    ///
+    /// ゲートウェイに接続済みで、侍データが1件以上読み込めている場合に `true` になります。
+    /// Ollama はザウルス機能のみで使うため、準備完了の条件には含めません。
+    pub fn is_ready(&self) -> bool {
+        self.gateway_connected.load(Ordering::SeqCst) && matches!(self.samurai, Ok(n) if n > 0)
+    }
+
+    /// 稼働状態の詳細を JSON で返す関数
+    /// # 戻り値
+    /// * `Value` - ゲートウェイ・侍データ・Ollama の状態
+    pub fn details(&self) -> Value {
+        let ollama = self
+            .ollama
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default();
+
+        json!({
+            "ready": self.is_ready(),
+            "gateway": {
+                "connected": self.gateway_connected.load(Ordering::SeqCst),
+            },
+            "samurai": {
+                "loaded": self.samurai.is_ok(),
+                "entries": self.samurai.as_ref().copied().unwrap_or(0),
+                "error": self.samurai.as_ref().err(),
+            },
+            "ollama": {
+                "last_success_unix": ollama.last_success.map(unix_secs),
+                "last_failure_unix": ollama.last_failure.map(unix_secs),
+                "last_error": ollama.last_error,
+            },
+        })
+    }
+}
+
+/// SystemTime を UNIX 秒に変換する関数
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_requires_gateway_and_dataset() {
+        let state = HealthState::new(Ok(3));
+        assert!(!state.is_ready());
+
+        state.set_gateway_connected(true);
+        assert!(state.is_ready());
+
+        let empty = HealthState::new(Ok(0));
+        empty.set_gateway_connected(true);
+        assert!(!empty.is_ready());
+
+        let failed = HealthState::new(Err("No such file".to_string()));
+        failed.set_gateway_connected(true);
+        assert!(!failed.is_ready());
+    }
+
+    #[test]
+    fn details_report_dataset_and_ollama_status() {
+        let state = HealthState::new(Err("No such file".to_string()));
+        state.record_ollama_success();
+        state.record_ollama_failure("connection refused");
+
+        let details = state.details();
+        assert_eq!(details["ready"], false);
+        assert_eq!(details["gateway"]["connected"], false);
+        assert_eq!(details["samurai"]["loaded"], false);
+        assert_eq!(details["samurai"]["entries"], 0);
+        assert_eq!(details["samurai"]["error"], "No such file");
+        assert!(details["ollama"]["last_success_unix"].is_u64());
+        assert!(details["ollama"]["last_failure_unix"].is_u64());
+        assert_eq!(details["ollama"]["last_error"], "connection refused");
+    }
+}
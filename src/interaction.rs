@@ -0,0 +1,255 @@
+use crate::buttons::{self, ButtonAction, ButtonId};
+use crate::config::CooldownConfig;
+use crate::cooldown::CooldownDecision;
+use crate::detect::Trigger;
+use crate::handler::{self, HandlerDeps};
+use crate::platform::{ComponentPress, IncomingMessage, InteractionResponder};
+use crate::table::{self, SamuraiEntry};
+use crate::template;
+use anyhow::Result;
+use log::{error, info};
+
+/// ボタンの有効期限が切れていたときの応答
+const EXPIRED_MESSAGE: &str = "このボタンの有効期限が切れました。もう一度「侍」と送ってください。";
+/// 呼び出した本人以外が「もう一回」を押したときの応答
+const NOT_OWNER_MESSAGE: &str = "「もう一回」は侍を呼んだ人だけが使えます。";
+/// 「もう一回」の回数制限に達したときの応答
+const REROLL_LIMITED_MESSAGE: &str =
+    "「もう一回」はしばらく使えません。少し待ってから試してください。";
+/// 侍データが見つからなかったときの応答
+const NOT_FOUND_MESSAGE: &str = "侍が見つかりませんでした。";
+
+/// ボタンが押されたときの処理（custom_id に応じて振り分ける）
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+/// * `responder` - インタラクションへの応答先
+/// * `press` - 押されたボタンの情報
+pub async fn handle_component(
+    deps: &HandlerDeps,
+    responder: &dyn InteractionResponder,
+    press: &ComponentPress,
+) {
+    // --- このボットの侍ボタン以外は無視 ---
+    let Some(id) = ButtonId::parse(&press.custom_id) else {
+        info!("Ignoring unknown component: {}", press.custom_id);
+        return;
+    };
+
+    // --- シャットダウン中であれば受け付けない ---
+    let Some(_in_flight) = deps.shutdown.try_begin() else {
+        info!("Shutting down; ignoring component: {}", press.custom_id);
+        return;
+    };
+
+    let result = if id.is_expired(deps.clock.now(), deps.buttons.expiry_secs) {
+        responder.respond_ephemeral(EXPIRED_MESSAGE).await
+    } else {
+        match id.action {
+            ButtonAction::Reroll => reroll(deps, responder, press, &id).await,
+            ButtonAction::Detail => show_detail(deps, responder, &id).await,
+            ButtonAction::Favorite => add_favorite(deps, responder, press, &id).await,
+        }
+    };
+    if let Err(e) = result {
+        error!("{:?}", e);
+    }
+}
+
+/// 「もう一回」: 埋め込みを新しい侍に差し替える
+async fn reroll(
+    deps: &HandlerDeps,
+    responder: &dyn InteractionResponder,
+    press: &ComponentPress,
+    id: &ButtonId,
+) -> Result<()> {
+    if press.user_id != id.owner_id {
+        return responder.respond_ephemeral(NOT_OWNER_MESSAGE).await;
+    }
+
+    // --- ユーザーごとの回数制限（トークンバケット） ---
+    let limit = CooldownConfig {
+        user: Some(deps.buttons.reroll),
+        ..Default::default()
+    };
+    let user = IncomingMessage {
+        id: press.message_id,
+        channel_id: press.channel_id,
+        guild_id: press.guild_id,
+        author_id: press.user_id,
+        author_name: press.user_name.clone(),
+        ..Default::default()
+    };
+    if deps.rerolls.check(Trigger::Samurai, &limit, &user) != CooldownDecision::Allowed {
+        info!("Reroll is limited for user: {}", press.user_name);
+        return responder.respond_ephemeral(REROLL_LIMITED_MESSAGE).await;
+    }
+
+    let Some(entry) = deps
+        .rng
+        .with(|rng| table::get_samurai_name(&deps.samurai_entries, rng))?
+    else {
+        return responder.respond_ephemeral(NOT_FOUND_MESSAGE).await;
+    };
+    info!("Rerolled samurai for {}: {}", press.user_name, entry.name);
+    deps.collection.record_draw(press.user_id, entry.id);
+    let buttons = buttons::samurai_buttons(entry.id, id.owner_id, deps.clock.now());
+    let embed = handler::samurai_embed(&deps.samurai_reply, &entry, true);
+    responder.update_embed(&embed, &buttons).await
+}
+
+/// 「詳細」: 説明の全文を押した本人にだけ表示する
+async fn show_detail(
+    deps: &HandlerDeps,
+    responder: &dyn InteractionResponder,
+    id: &ButtonId,
+) -> Result<()> {
+    let Some(entry) = find_entry(deps, id) else {
+        return responder.respond_ephemeral(NOT_FOUND_MESSAGE).await;
+    };
+    let content = format!(
+        "**{}**\n{}",
+        template::escape_markdown(&template::escape_mentions(&entry.name)),
+        template::escape_mentions(&entry.description)
+    );
+    responder.respond_ephemeral(&content).await
+}
+
+/// 「お気に入り」: 表示中の侍を押した本人のお気に入りに追加する
+async fn add_favorite(
+    deps: &HandlerDeps,
+    responder: &dyn InteractionResponder,
+    press: &ComponentPress,
+    id: &ButtonId,
+) -> Result<()> {
+    let Some(entry) = find_entry(deps, id) else {
+        return responder.respond_ephemeral(NOT_FOUND_MESSAGE).await;
+    };
+    let name = template::escape_markdown(&template::escape_mentions(&entry.name));
+    let content = if deps.collection.add_favorite(press.user_id, entry.id) {
+        format!("⭐ {} をお気に入りに追加しました。", name)
+    } else {
+        format!("{} はすでにお気に入りに入っています。", name)
+    };
+    responder.respond_ephemeral(&content).await
+}
+
+/// ボタンに埋め込まれた ID の侍を探す
+fn find_entry<'a>(deps: &'a HandlerDeps, id: &ButtonId) -> Option<&'a SamuraiEntry> {
+    deps.samurai_entries.get(id.entry_id as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::tests::{FixedChat, NOW, deps};
+    use crate::platform::{RecordedAction, RecordingPlatform};
+
+    fn press(action: ButtonAction, user_id: u64, issued_at: u64) -> ComponentPress {
+        ComponentPress {
+            custom_id: ButtonId {
+                action,
+                entry_id: 0,
+                owner_id: 40,
+                issued_at,
+            }
+            .encode(),
+            message_id: 1,
+            channel_id: 20,
+            guild_id: Some(30),
+            user_id,
+            user_name: "user".to_string(),
+        }
+    }
+
+    fn ephemeral(platform: &RecordingPlatform) -> String {
+        match platform.take_actions().as_slice() {
+            [RecordedAction::Ephemeral { content }] => content.clone(),
+            other => panic!("unexpected actions: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reroll_replaces_embed_for_owner_only() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+
+        handle_component(&deps, &platform, &press(ButtonAction::Reroll, 40, NOW)).await;
+        match platform.take_actions().as_slice() {
+            [RecordedAction::UpdateEmbed { embed, buttons }] => {
+                assert_eq!(embed.title, "テスト侍");
+                let id = ButtonId::parse(&buttons[0].custom_id).unwrap();
+                assert_eq!(id.owner_id, 40);
+            }
+            other => panic!("unexpected actions: {:?}", other),
+        }
+
+        handle_component(&deps, &platform, &press(ButtonAction::Reroll, 41, NOW)).await;
+        assert_eq!(ephemeral(&platform), NOT_OWNER_MESSAGE);
+    }
+
+    #[tokio::test]
+    async fn reroll_is_rate_limited_per_user() {
+        let platform = RecordingPlatform::new();
+        let mut deps = deps(FixedChat(None));
+        deps.buttons.reroll.capacity = 1;
+
+        handle_component(&deps, &platform, &press(ButtonAction::Reroll, 40, NOW)).await;
+        assert!(matches!(
+            platform.take_actions()[0],
+            RecordedAction::UpdateEmbed { .. }
+        ));
+        handle_component(&deps, &platform, &press(ButtonAction::Reroll, 40, NOW)).await;
+        assert_eq!(ephemeral(&platform), REROLL_LIMITED_MESSAGE);
+    }
+
+    #[tokio::test]
+    async fn detail_and_favorite_respond_ephemerally() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+
+        handle_component(&deps, &platform, &press(ButtonAction::Detail, 41, NOW)).await;
+        assert_eq!(ephemeral(&platform), "**テスト侍**\nテストテストテスト");
+
+        handle_component(&deps, &platform, &press(ButtonAction::Favorite, 41, NOW)).await;
+        assert_eq!(
+            ephemeral(&platform),
+            "⭐ テスト侍 をお気に入りに追加しました。"
+        );
+        handle_component(&deps, &platform, &press(ButtonAction::Favorite, 41, NOW)).await;
+        assert_eq!(
+            ephemeral(&platform),
+            "テスト侍 はすでにお気に入りに入っています。"
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_and_unknown_buttons() {
+        let platform = RecordingPlatform::new();
+        let deps = deps(FixedChat(None));
+
+        // 有効期限ちょうどまでは使え、それを過ぎると期限切れになる
+        let expiry = deps.buttons.expiry_secs;
+        handle_component(
+            &deps,
+            &platform,
+            &press(ButtonAction::Detail, 40, NOW - expiry),
+        )
+        .await;
+        assert_ne!(ephemeral(&platform), EXPIRED_MESSAGE);
+        let issued_at = NOW - expiry - 1;
+        handle_component(
+            &deps,
+            &platform,
+            &press(ButtonAction::Detail, 40, issued_at),
+        )
+        .await;
+        assert_eq!(ephemeral(&platform), EXPIRED_MESSAGE);
+
+        let unknown = ComponentPress {
+            custom_id: "other:button".to_string(),
+            ..Default::default()
+        };
+        handle_component(&deps, &platform, &unknown).await;
+        assert!(platform.take_actions().is_empty());
+    }
+}
@@ -2,29 +2,60 @@ use anyhow::Context as anyhowContext;
 use anyhow::Error;
 use once_cell::sync::Lazy;
 use serenity::async_trait;
+use serenity::gateway::{ConnectionStage, ShardStageUpdateEvent};
 use serenity::model::{channel::Message, gateway::Ready, prelude::*};
 use serenity::prelude::*;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+mod access;
+mod buttons;
 mod chat;
+mod clock;
+mod collection;
+mod commands;
 mod config;
+mod cooldown;
+mod daily;
 mod detect;
 mod discord;
+mod gate;
+mod handler;
+mod health;
+mod interaction;
+mod memory;
+mod metrics;
+mod persist;
+mod platform;
+mod random;
+mod repl;
+mod replies;
+mod server;
+mod shutdown;
+mod stats;
 mod table;
-use log::{error, info};
+mod template;
+use log::{error, info, warn};
 use table::SamuraiEntry;
 
 // イベントハンドラ用構造体
 struct Handler;
 
-struct OllamaChatKey;
+struct HandlerDepsKey;
 
-impl TypeMapKey for OllamaChatKey {
-    type Value = Arc<chat::OllamaChat>;
+impl TypeMapKey for HandlerDepsKey {
+    type Value = Arc<handler::HandlerDeps>;
 }
 
+// 「今日の侍」の定期投稿を開始済みかどうか（再接続のたびに ready が呼ばれるため）
+static DAILY_STARTED: AtomicBool = AtomicBool::new(false);
+
+// 変更をファイルへ書き出す間隔
+const PERSIST_INTERVAL: Duration = Duration::from_secs(10);
+
 // テーブル
 static SAMURAI_DATA: Lazy<Result<Vec<SamuraiEntry>, Error>> =
-    Lazy::new(table::read_samurai_csv_as_vec);
+    Lazy::new(table::read_samurai_entries);
 
 #[async_trait]
 impl EventHandler for Handler {
@@ -33,79 +64,157 @@ impl EventHandler for Handler {
     /// * `ctx` - コンテキスト (メッセージの送信先やボットの情報など)
     /// * `msg` - 送信されたメッセージ
     async fn message(&self, ctx: Context, msg: Message) {
-        // --- メッセージがボットからのものであれば無視 ---
-        if msg.author.bot {
+        metrics::METRICS.messages_seen.inc();
+
+        let deps = {
+            let data = ctx.data.read().await;
+            data.get::<HandlerDepsKey>().cloned()
+        };
+
+        let Some(deps) = deps else {
+            error!("HandlerDeps is not initialized in client data");
             return;
-        }
+        };
+
+        // --- 判定・応答はプラットフォーム非依存のハンドラに委譲 ---
+        let platform = discord::SerenityPlatform::new(&ctx);
+        let incoming = discord::incoming_message(&ctx, &msg);
+        handler::handle_message(&deps, &platform, &incoming).await;
+    }
 
-        // --- メッセージがダイレクトメッセージであれば無視 ---
-        if msg.guild_id.is_none() {
-            info!("Received DM from user: {}", msg.author.name);
+    /// メッセージが編集されたときに呼ばれる関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `_old_if_available` - 編集前のメッセージ（キャッシュにある場合）
+    /// * `_new` - 編集後のメッセージ（キャッシュにある場合）
+    /// * `event` - 編集イベント
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        let deps = {
+            let data = ctx.data.read().await;
+            data.get::<HandlerDepsKey>().cloned()
+        };
+        let Some(deps) = deps else {
+            error!("HandlerDeps is not initialized in client data");
             return;
-        }
+        };
 
-        // --- メッセージの内容から侍を検出 ---
-        let is_samurai = detect::contains_samurai_phrase(&msg.content);
-        let is_zaurus = detect::contains_zaurus_phrase(&msg.content);
+        // 本文を含まない編集イベント（埋め込みの展開など）は無視する
+        let Some(incoming) = discord::incoming_message_update(&ctx, &event) else {
+            return;
+        };
+        let platform = discord::SerenityPlatform::new(&ctx);
+        handler::handle_message_update(&deps, &platform, &incoming).await;
+    }
 
-        if is_samurai {
-            info!("Received '侍' from user: {}", msg.author.name);
+    /// メッセージが削除されたときに呼ばれる関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `channel_id` - 削除されたメッセージのチャンネルID
+    /// * `deleted_message_id` - 削除されたメッセージID
+    /// * `guild_id` - サーバーID（DM の場合は `None`）
+    async fn message_delete(
+        &self,
+        ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        guild_id: Option<GuildId>,
+    ) {
+        self.message_delete_bulk(ctx, channel_id, vec![deleted_message_id], guild_id)
+            .await;
+    }
 
-            // --- メッセージの内容に応じてリアクションを実行 ---
-            discord::samurai_reaction(&ctx, &msg).await;
+    /// メッセージがまとめて削除されたときに呼ばれる関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `_channel_id` - 削除されたメッセージのチャンネルID
+    /// * `multiple_deleted_messages_ids` - 削除されたメッセージIDの一覧
+    /// * `_guild_id` - サーバーID（DM の場合は `None`）
+    async fn message_delete_bulk(
+        &self,
+        ctx: Context,
+        _channel_id: ChannelId,
+        multiple_deleted_messages_ids: Vec<MessageId>,
+        _guild_id: Option<GuildId>,
+    ) {
+        let deps = {
+            let data = ctx.data.read().await;
+            data.get::<HandlerDepsKey>().cloned()
+        };
+        let Some(deps) = deps else {
+            error!("HandlerDeps is not initialized in client data");
+            return;
+        };
 
-            // --- SAMURAI_DATAからDataFrameを取得 ---
-            let df = match &*SAMURAI_DATA {
-                Ok(data) => data,
-                Err(e) => {
-                    error!("Error: {}", e);
-                    return;
-                }
-            };
-
-            // --- ランダムな侍を過去データから取得 ---
-            let sname_res = table::get_samurai_name(df); // Samurai ID を取得
-            let sname = match sname_res {
-                Ok(Some(name)) => name,
-                Ok(None) => {
-                    error!("Samurai not found");
-                    return;
-                }
-                Err(e) => {
-                    error!("Error: {}", e);
-                    return;
-                }
-            };
-            info!("Samurai name: {}", sname);
-
-            // --- メッセージにリプライ ---
-            discord::samurai_reply(&ctx, &msg, &sname).await;
-            info!("Replied to message: {}", msg.id);
-        } else if is_zaurus {
-            info!("Received 'ザウルス' from user: {}", msg.author.name);
-
-            // --- メッセージの内容に応じてチャットボットで応答 ---
-            let chat_client = {
-                let data = ctx.data.read().await;
-                data.get::<OllamaChatKey>().cloned()
-            };
-
-            let Some(chat_client) = chat_client else {
-                error!("OllamaChat is not initialized in client data");
+        let platform = discord::SerenityPlatform::new(&ctx);
+        for message_id in multiple_deleted_messages_ids {
+            handler::handle_message_delete(&deps, &platform, message_id.get()).await;
+        }
+    }
+
+    /// メッセージにリアクションが付けられたときに呼ばれる関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `add_reaction` - 追加されたリアクション
+    async fn reaction_add(&self, ctx: Context, add_reaction: Reaction) {
+        let deps = {
+            let data = ctx.data.read().await;
+            data.get::<HandlerDepsKey>().cloned()
+        };
+        let Some(deps) = deps else {
+            error!("HandlerDeps is not initialized in client data");
+            return;
+        };
+
+        // トリガーに設定されていないリアクションでは、メッセージを取得しない
+        let Some(emoji) = discord::reaction_emoji(&add_reaction) else {
+            return;
+        };
+        if deps.matcher.find_reaction(&emoji).is_empty() {
+            return;
+        }
+        let incoming = match discord::incoming_reaction(&ctx, &add_reaction).await {
+            Ok(incoming) => incoming,
+            Err(e) => {
+                error!("{:?}", e);
                 return;
-            };
+            }
+        };
+        let platform = discord::SerenityPlatform::new(&ctx);
+        handler::handle_reaction_add(&deps, &platform, &incoming, &emoji).await;
+    }
 
-            let reply = match chat_client.chat_once(&msg.content).await {
-                Ok(text) => text,
-                Err(e) => {
-                    error!("Error chatting with Ollama: {:?}", e);
-                    return;
-                }
-            };
+    /// インタラクション（ボタンの押下など）を受け取ったときに呼ばれる関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `interaction` - 受け取ったインタラクション
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let deps = {
+            let data = ctx.data.read().await;
+            data.get::<HandlerDepsKey>().cloned()
+        };
+        let Some(deps) = deps else {
+            error!("HandlerDeps is not initialized in client data");
+            return;
+        };
 
-            // --- メッセージにリプライ ---
-            discord::zaurus_reply(&ctx, &msg, &reply).await;
-            info!("Replied to message: {}", msg.id);
+        match interaction {
+            Interaction::Component(component) => {
+                let press = discord::component_press(&component);
+                let responder = discord::SerenityInteraction::component(&ctx, component);
+                interaction::handle_component(&deps, &responder, &press).await;
+            }
+            Interaction::Command(command) => {
+                let cmd = discord::command_invocation(&command);
+                let responder = discord::SerenityInteraction::command(&ctx, command);
+                commands::handle_command(&deps, &responder, &cmd).await;
+            }
+            _ => {}
         }
     }
 
@@ -113,9 +222,206 @@ impl EventHandler for Handler {
     /// # 引数
     /// * `ctx` - コンテキスト (メッセージの送信先やボットの情報など)
     /// * `ready` - ボットの準備が完了したことを示す情報（ユーザー名など）
-    async fn ready(&self, _: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         info!("{} is connected!", ready.user.name);
+        let deps = ctx.data.read().await.get::<HandlerDepsKey>().cloned();
+        if let Some(deps) = deps {
+            deps.health.set_gateway_connected(true);
+
+            // --- 「今日の侍」の定期投稿を開始 ---
+            if !DAILY_STARTED.swap(true, Ordering::SeqCst) {
+                tokio::spawn(daily::run(deps, discord::SerenityPlatform::new(&ctx)));
+            }
+        }
+
+        // --- スラッシュコマンドを登録 ---
+        match discord::register_commands(&ctx).await {
+            Ok(()) => info!("Registered slash commands"),
+            Err(e) => error!("{:?}", e),
+        }
     }
+
+    /// シャードの接続状態が変化したときに呼ばれる関数
+    /// # 引数
+    /// * `ctx` - コンテキスト
+    /// * `event` - 変化前後の接続状態
+    async fn shard_stage_update(&self, ctx: Context, event: ShardStageUpdateEvent) {
+        info!(
+            "Shard {} stage changed: {} -> {}",
+            event.shard_id, event.old, event.new
+        );
+        if let Some(deps) = ctx.data.read().await.get::<HandlerDepsKey>() {
+            deps.health
+                .set_gateway_connected(event.new == ConnectionStage::Connected);
+        }
+    }
+}
+
+/// 起動モード
+/// * `Bot` - Discord に接続して動作する（デフォルト）
+/// * `Repl { stub }` - 標準入力から判定・応答を試す（`stub` が真なら Ollama の代わりにスタブを使う）
+enum RunMode {
+    Bot,
+    Repl { stub: bool },
+}
+
+/// コマンドライン引数から起動モードを決定する関数
+/// # 引数
+/// * `args` - プログラム名を除いたコマンドライン引数
+/// # 戻り値
+/// * `RunMode` - 起動モード
+fn parse_run_mode(args: &[String]) -> RunMode {
+    match args.first().map(String::as_str) {
+        Some("repl") => RunMode::Repl {
+            stub: args.iter().skip(1).any(|a| a == "--stub"),
+        },
+        _ => RunMode::Bot,
+    }
+}
+
+/// メッセージ処理に必要な依存を構築する関数（設定に応じてファイルに永続化する）
+/// # 引数
+/// * `chat` - ザウルス応答に使うチャットバックエンド
+/// # 戻り値
+/// * `Ok(HandlerDeps)` - メッセージ処理に必要な依存
+/// * `Err(anyhow::Error)` - トリガーの検知器を作成できない場合
+fn build_handler_deps(chat: Arc<dyn chat::ChatBackend>) -> anyhow::Result<handler::HandlerDeps> {
+    build_deps(
+        chat,
+        build_reply_tracker(),
+        build_collection(),
+        build_stats(),
+    )
+}
+
+/// REPL モード用の依存を構築する関数
+///
+/// 入力した行が本番のランキング・コレクション・返信の対応のファイルに書き込まれないよう、
+/// これらはメモリ上のみで保持します。
+/// # 引数
+/// * `chat` - ザウルス応答に使うチャットバックエンド
+fn build_repl_deps(chat: Arc<dyn chat::ChatBackend>) -> anyhow::Result<handler::HandlerDeps> {
+    build_deps(
+        chat,
+        replies::ReplyTracker::new(),
+        collection::Collection::new(),
+        stats::TriggerStats::new(),
+    )
+}
+
+/// 状態を保持するストアを受け取って、メッセージ処理に必要な依存を構築する関数
+/// # 引数
+/// * `chat` - ザウルス応答に使うチャットバックエンド
+/// * `replies` - 元メッセージと返信の対応
+/// * `collection` - 侍コレクション
+/// * `stats` - ランキング用のトリガー回数
+fn build_deps(
+    chat: Arc<dyn chat::ChatBackend>,
+    replies: replies::ReplyTracker,
+    collection: collection::Collection,
+    stats: stats::TriggerStats,
+) -> anyhow::Result<handler::HandlerDeps> {
+    // --- 利用データの取得 ---
+    let (samurai_entries, samurai_status) = match &*SAMURAI_DATA {
+        Ok(data) => {
+            info!("{:?}", data);
+            (data.clone(), Ok(data.len()))
+        }
+        Err(e) => {
+            error!("Error: {}", e);
+            (Vec::new(), Err(format!("{:#}", e)))
+        }
+    };
+
+    Ok(handler::HandlerDeps {
+        chat,
+        samurai_entries: Arc::new(samurai_entries),
+        shutdown: Arc::new(shutdown::Shutdown::new()),
+        health: Arc::new(health::HealthState::new(samurai_status)),
+        triggers: config::app_config().triggers.clone(),
+        matcher: detect::TriggerMatcher::new(&config::app_config().triggers)
+            .context("Failed to build trigger matcher")?,
+        cooldowns: cooldown::Cooldowns::new(),
+        dm: config::app_config().dm.clone(),
+        memory: memory::ConversationMemory::new(config::app_config().dm.memory_turns),
+        edits: config::app_config().edits.clone(),
+        replies,
+        samurai_reply: config::app_config().samurai_reply.clone(),
+        templates: config::app_config().templates.clone(),
+        buttons: config::app_config().buttons.clone(),
+        rerolls: cooldown::Cooldowns::new(),
+        collection,
+        stats,
+        daily: config::app_config().daily.clone(),
+        clock: Arc::new(clock::SystemClock),
+        rng: build_rng(),
+    })
+}
+
+/// 設定に応じて乱数生成器を作成する関数
+/// （シードが指定されていなければ OS の乱数でシードする）
+fn build_rng() -> random::SharedRng {
+    match config::app_config().random_seed {
+        Some(seed) => {
+            info!("Using random seed: {}", seed);
+            random::SharedRng::seeded(seed)
+        }
+        None => random::SharedRng::from_os_rng(),
+    }
+}
+
+/// 設定に応じてランキング用のトリガー回数を作成する関数
+/// （読み込みに失敗した場合はメモリ上のみで保持する）
+fn build_stats() -> stats::TriggerStats {
+    let Some(path) = &config::app_config().stats_path else {
+        return stats::TriggerStats::new();
+    };
+    match stats::TriggerStats::with_persistence(path) {
+        Ok(stats) => stats,
+        Err(e) => {
+            error!("Error loading trigger stats: {:?}", e);
+            stats::TriggerStats::new()
+        }
+    }
+}
+
+/// 設定に応じて侍コレクションを作成する関数
+/// （読み込みに失敗した場合はメモリ上のみで保持する）
+fn build_collection() -> collection::Collection {
+    let Some(path) = &config::app_config().collection_path else {
+        return collection::Collection::new();
+    };
+    match collection::Collection::with_persistence(path) {
+        Ok(collection) => collection,
+        Err(e) => {
+            error!("Error loading samurai collection: {:?}", e);
+            collection::Collection::new()
+        }
+    }
+}
+
+/// 設定に応じて返信の対応を保持する ReplyTracker を作成する関数
+/// （読み込みに失敗した場合はメモリ上のみで保持する）
+fn build_reply_tracker() -> replies::ReplyTracker {
+    let Some(path) = &config::app_config().replies_path else {
+        return replies::ReplyTracker::new();
+    };
+    match replies::ReplyTracker::with_persistence(path) {
+        Ok(tracker) => tracker,
+        Err(e) => {
+            error!("Error loading tracked replies: {:?}", e);
+            replies::ReplyTracker::new()
+        }
+    }
+}
+
+/// ファイルに永続化するストアの変更を書き出す関数（ブロッキング I/O を行う）
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+fn flush_stores(deps: &handler::HandlerDeps) {
+    deps.collection.flush();
+    deps.stats.flush();
+    deps.replies.flush();
 }
 
 #[tokio::main]
@@ -127,6 +433,26 @@ async fn main() {
     let app_config = config::init_app_config()
         .context("Failed to initialize configuration")
         .expect("Configuration is required to start the bot");
+
+    // --- REPL モード ---
+    // Discord に接続せず、標準入力の各行を同じ判定・応答処理に通す
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let RunMode::Repl { stub } = parse_run_mode(&args) {
+        let chat: Arc<dyn chat::ChatBackend> = if stub {
+            Arc::new(chat::StubChat)
+        } else {
+            Arc::new(chat::OllamaChat::new())
+        };
+        let result = match build_repl_deps(chat) {
+            Ok(deps) => repl::run(&deps).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error!("REPL error: {:?}", e);
+        }
+        return;
+    }
+
     let token = &app_config.discord_token;
 
     // --- インテントの設定 ---
@@ -134,10 +460,14 @@ async fn main() {
     let intents = GatewayIntents::GUILD_MESSAGES // サーバー内のメッセージ受信
         | GatewayIntents::MESSAGE_CONTENT   // ★メッセージの内容を読む (特権インテント)
         | GatewayIntents::GUILDS            // サーバー情報の取得 (キャッシュや絵文字検索に必要)
-        | GatewayIntents::GUILD_EMOJIS_AND_STICKERS; // サーバーの絵文字リスト取得
-
-    // チャットボットクライアントの起動（mainで初期化して共有）
-    let chat_client = Arc::new(chat::OllamaChat::new());
+        | GatewayIntents::GUILD_EMOJIS_AND_STICKERS // サーバーの絵文字リスト取得
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS; // リアクションによるトリガーの検知
+    // DM モードが有効な場合のみダイレクトメッセージを受信する
+    let intents = if app_config.dm.enabled {
+        intents | GatewayIntents::DIRECT_MESSAGES
+    } else {
+        intents
+    };
 
     // --- クライアントの構築 ---
     let mut client = Client::builder(token, intents)
@@ -145,19 +475,69 @@ async fn main() {
         .await
         .expect("Error creating client");
 
+    // チャットボットクライアントの起動（mainで初期化して共有）
+    let deps = match build_handler_deps(Arc::new(chat::OllamaChat::new())) {
+        Ok(deps) => Arc::new(deps),
+        Err(e) => {
+            error!("{:?}", e);
+            return;
+        }
+    };
     {
         let mut data = client.data.write().await;
-        data.insert::<OllamaChatKey>(Arc::clone(&chat_client));
+        data.insert::<HandlerDepsKey>(Arc::clone(&deps));
     }
 
-    // --- 利用データの取得 ---
-    match &*SAMURAI_DATA {
-        Ok(data) => info!("{:?}", data),
-        Err(e) => error!("Error: {}", e),
+    // --- ヘルスチェック用 HTTP サーバーの起動 ---
+    if let Some(addr) = &app_config.health_addr {
+        server::spawn(addr.clone(), Arc::clone(&deps.health));
+    }
+
+    // --- ストアの変更の定期的な書き出し ---
+    // ハンドラを止めないよう、書き出しはブロッキング用のスレッドで行う
+    {
+        let deps = Arc::clone(&deps);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+            loop {
+                interval.tick().await;
+                let deps = Arc::clone(&deps);
+                if let Err(e) = tokio::task::spawn_blocking(move || flush_stores(&deps)).await {
+                    error!("Failed to flush stores: {:?}", e);
+                }
+            }
+        });
     }
 
+    // --- シグナル受信時のグレースフルシャットダウン ---
+    // 新しいトリガーの受け付けを止め、処理中の応答を待ってからシャードを停止する
+    let shard_manager = Arc::clone(&client.shard_manager);
+    let shutdown_timeout = Duration::from_secs(app_config.shutdown_timeout_secs);
+    tokio::spawn(async move {
+        shutdown::wait_for_signal().await;
+        info!("Shutting down: no longer accepting new triggers");
+        deps.shutdown.begin();
+
+        if !deps.shutdown.wait_idle(shutdown_timeout).await {
+            warn!(
+                "Timed out after {:?} waiting for {} in-flight message(s)",
+                shutdown_timeout,
+                deps.shutdown.in_flight()
+            );
+        }
+
+        // 定期的な書き出しの後に残った変更を書き出す
+        let flushing = Arc::clone(&deps);
+        if let Err(e) = tokio::task::spawn_blocking(move || flush_stores(&flushing)).await {
+            error!("Failed to flush stores: {:?}", e);
+        }
+        shard_manager.shutdown_all().await;
+        deps.health.set_gateway_connected(false);
+    });
+
     // --- ボットの起動 ---
     if let Err(why) = client.start().await {
         error!("Client error: {:?}", why);
     }
+    info!("Shutdown complete");
 }
@@ -0,0 +1,93 @@
+use crate::chat::Message;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// ユーザーごとの会話履歴を保持する構造体
+///
+/// DM でのザウルスとの会話に使い、ユーザーごとに直近のやり取りだけを残します。
+#[derive(Debug)]
+pub struct ConversationMemory {
+    max_turns: usize,
+    histories: Mutex<HashMap<u64, VecDeque<Message>>>,
+}
+
+impl ConversationMemory {
+    /// 新しい ConversationMemory を作成する関数
+    /// # 引数
+    /// * `max_turns` - ユーザーごとに保持する往復数（0 の場合は履歴を残さない）
+    pub fn new(max_turns: usize) -> Self {
+        Self {
+            max_turns,
+            histories: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// ユーザーの会話履歴を取得する関数
+    /// # 引数
+    /// * `user_id` - ユーザーID
+    /// # 戻り値
+    /// * `Vec<Message>` - 古い順の会話履歴
+    pub fn history(&self, user_id: u64) -> Vec<Message> {
+        self.histories
+            .lock()
+            .ok()
+            .and_then(|histories| histories.get(&user_id).map(|h| h.iter().cloned().collect()))
+            .unwrap_or_default()
+    }
+
+    /// 1往復分の会話を記録する関数（上限を超えた古い往復は捨てる）
+    /// # 引数
+    /// * `user_id` - ユーザーID
+    /// * `user_input` - ユーザーの発言
+    /// * `reply` - アシスタントの応答
+    pub fn record(&self, user_id: u64, user_input: &str, reply: &str) {
+        if self.max_turns == 0 {
+            return;
+        }
+        let Ok(mut histories) = self.histories.lock() else {
+            return;
+        };
+        let history = histories.entry(user_id).or_default();
+        history.push_back(Message {
+            role: "user".to_string(),
+            content: user_input.to_string(),
+        });
+        history.push_back(Message {
+            role: "assistant".to_string(),
+            content: reply.to_string(),
+        });
+        while history.len() > self.max_turns * 2 {
+            history.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_latest_turns_per_user() {
+        let memory = ConversationMemory::new(2);
+        memory.record(1, "a", "A");
+        memory.record(1, "b", "B");
+        memory.record(1, "c", "C");
+        memory.record(2, "x", "X");
+
+        let history = memory.history(1);
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["b", "B", "c", "C"]);
+        assert_eq!(history[0].role, "user");
+        assert_eq!(history[1].role, "assistant");
+
+        assert_eq!(memory.history(2).len(), 2);
+        assert!(memory.history(3).is_empty());
+    }
+
+    #[test]
+    fn zero_turns_disables_memory() {
+        let memory = ConversationMemory::new(0);
+        memory.record(1, "a", "A");
+        assert!(memory.history(1).is_empty());
+    }
+}
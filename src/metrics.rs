@@ -0,0 +1,261 @@
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Ollama の応答時間ヒストグラムのバケット（秒）
+const LATENCY_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0];
+/// Ollama の応答サイズヒストグラムのバケット（バイト）
+const RESPONSE_SIZE_BUCKETS: &[f64] = &[64.0, 256.0, 512.0, 1024.0, 2048.0, 4096.0, 8192.0];
+
+/// プロセス全体で共有するメトリクス
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+/// 単調増加するカウンター
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    /// カウンターを1増やす関数
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// ラベル1つで区別されるカウンターの集合
+#[derive(Debug, Default)]
+pub struct CounterVec(Mutex<BTreeMap<String, u64>>);
+
+impl CounterVec {
+    /// 指定ラベルのカウンターを1増やす関数
+    /// # 引数
+    /// * `label` - ラベルの値
+    pub fn inc(&self, label: &str) {
+        if let Ok(mut values) = self.0.lock() {
+            *values.entry(label.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0.lock().map(|v| v.clone()).unwrap_or_default()
+    }
+}
+
+/// 固定バケットのヒストグラム
+#[derive(Debug)]
+pub struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<AtomicU64>,
+    // f64 の合計値をビット列として保持する
+    sum_bits: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: buckets.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum_bits: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// 観測値を記録する関数
+    /// # 引数
+    /// * `value` - 観測値
+    pub fn observe(&self, value: f64) {
+        for (bound, count) in self.buckets.iter().zip(&self.counts) {
+            if value <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum_bits
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+}
+
+/// ボットの動作に関するメトリクス
+/// # フィールド
+/// * `messages_seen` - 受信したメッセージ数
+/// * `triggers_matched` - ルールごとのトリガー一致数
+/// * `reactions` - リアクションの結果（added / failed）ごとの件数
+/// * `replies` - 返信の結果（sent / failed）ごとの件数
+/// * `ollama_latency` - Ollama の応答時間（秒）
+/// * `ollama_response_bytes` - Ollama の応答サイズ（バイト）
+#[derive(Debug)]
+pub struct Metrics {
+    pub messages_seen: Counter,
+    pub triggers_matched: CounterVec,
+    pub reactions: CounterVec,
+    pub replies: CounterVec,
+    pub ollama_latency: Histogram,
+    pub ollama_response_bytes: Histogram,
+}
+
+impl Metrics {
+    /// 新しい Metrics を作成する関数
+    pub fn new() -> Self {
+        Self {
+            messages_seen: Counter::default(),
+            triggers_matched: CounterVec::default(),
+            reactions: CounterVec::default(),
+            replies: CounterVec::default(),
+            ollama_latency: Histogram::new(LATENCY_BUCKETS),
+            ollama_response_bytes: Histogram::new(RESPONSE_SIZE_BUCKETS),
+        }
+    }
+
+    /// Prometheus のテキスト形式で出力する関数
+    /// # 戻り値
+    /// * `String` - `/metrics` のレスポンスボディ
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        write_counter(
+            &mut out,
+            "kasu_messages_seen_total",
+            "Messages received by the bot",
+            self.messages_seen.get(),
+        );
+        write_counter_vec(
+            &mut out,
+            "kasu_triggers_matched_total",
+            "Messages that matched a trigger rule",
+            "rule",
+            &self.triggers_matched,
+        );
+        write_counter_vec(
+            &mut out,
+            "kasu_reactions_total",
+            "Reactions attempted by result",
+            "result",
+            &self.reactions,
+        );
+        write_counter_vec(
+            &mut out,
+            "kasu_replies_total",
+            "Replies attempted by result",
+            "result",
+            &self.replies,
+        );
+        write_histogram(
+            &mut out,
+            "kasu_ollama_request_duration_seconds",
+            "Latency of Ollama chat requests",
+            &self.ollama_latency,
+        );
+        write_histogram(
+            &mut out,
+            "kasu_ollama_response_bytes",
+            "Size of Ollama chat responses",
+            &self.ollama_response_bytes,
+        );
+        out
+    }
+}
+
+fn write_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn write_counter_vec(out: &mut String, name: &str, help: &str, label: &str, vec: &CounterVec) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (value, count) in vec.snapshot() {
+        let _ = writeln!(
+            out,
+            "{}{{{}=\"{}\"}} {}",
+            name,
+            label,
+            escape_label(&value),
+            count
+        );
+    }
+}
+
+fn write_histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (bound, count) in histogram.buckets.iter().zip(&histogram.counts) {
+        let _ = writeln!(
+            out,
+            "{}_bucket{{le=\"{}\"}} {}",
+            name,
+            bound,
+            count.load(Ordering::Relaxed)
+        );
+    }
+    let total = histogram.count.load(Ordering::Relaxed);
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, total);
+    let _ = writeln!(
+        out,
+        "{}_sum {}",
+        name,
+        f64::from_bits(histogram.sum_bits.load(Ordering::Relaxed))
+    );
+    let _ = writeln!(out, "{}_count {}", name, total);
+}
+
+/// ラベル値の `\`・`"`・改行をエスケープする関数
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_in_prometheus_format() {
+        let metrics = Metrics::new();
+        metrics.messages_seen.inc();
+        metrics.messages_seen.inc();
+        metrics.triggers_matched.inc("samurai");
+        metrics.replies.inc("sent");
+        metrics.replies.inc("failed");
+        metrics.replies.inc("sent");
+
+        let text = metrics.render();
+        assert!(
+            text.contains("# TYPE kasu_messages_seen_total counter\nkasu_messages_seen_total 2\n")
+        );
+        assert!(text.contains("kasu_triggers_matched_total{rule=\"samurai\"} 1\n"));
+        assert!(text.contains("kasu_replies_total{result=\"failed\"} 1\n"));
+        assert!(text.contains("kasu_replies_total{result=\"sent\"} 2\n"));
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::new();
+        metrics.ollama_latency.observe(0.2);
+        metrics.ollama_latency.observe(3.0);
+        metrics.ollama_latency.observe(100.0);
+
+        let text = metrics.render();
+        assert!(text.contains("kasu_ollama_request_duration_seconds_bucket{le=\"0.1\"} 0\n"));
+        assert!(text.contains("kasu_ollama_request_duration_seconds_bucket{le=\"0.25\"} 1\n"));
+        assert!(text.contains("kasu_ollama_request_duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(text.contains("kasu_ollama_request_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("kasu_ollama_request_duration_seconds_sum 103.2\n"));
+        assert!(text.contains("kasu_ollama_request_duration_seconds_count 3\n"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// 変更をためておき、まとめて書き出す JSON ファイル
+///
+/// 変更のたびにファイル全体を書き直すと、非同期のハンドラがブロッキング I/O で止まるため、
+/// 変更があったことだけを記録し、`flush` で書き出します（定期的な書き出しとシャットダウン時に呼ぶ）。
+/// # フィールド
+/// * `path` - 書き出す JSON ファイルのパス
+/// * `dirty` - 前回の書き出し以降に変更があったか
+/// * `writing` - 書き出しを1つずつ行うためのロック（古い内容で上書きしないように）
+#[derive(Debug)]
+pub struct JsonFile {
+    path: PathBuf,
+    dirty: AtomicBool,
+    writing: Mutex<()>,
+}
+
+impl JsonFile {
+    /// 新しい JsonFile を作成する関数
+    /// # 引数
+    /// * `path` - 書き出す JSON ファイルのパス
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            dirty: AtomicBool::new(false),
+            writing: Mutex::new(()),
+        }
+    }
+
+    /// 変更があったことを記録する関数（ファイルにはまだ書き出さない）
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// 変更があれば、現在の内容をファイルに書き出す関数（ブロッキング I/O を行う）
+    /// # 引数
+    /// * `snapshot` - 書き出す内容を作成する処理（変更がなければ呼ばれない）
+    /// # 戻り値
+    /// * `Err(anyhow::Error)` - 書き出しに失敗した場合（次回の `flush` で再び書き出す）
+    pub fn flush<T: Serialize>(&self, snapshot: impl FnOnce() -> T) -> Result<()> {
+        let _writing = self.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let result = save_json_atomic(&self.path, &snapshot());
+        if result.is_err() {
+            self.mark_dirty();
+        }
+        result
+    }
+}
+
+/// JSON ファイルを読み込む関数（ファイルが存在しなければ既定値を返す）
+/// # 引数
+/// * `path` - 読み込む JSON ファイルのパス
+/// # 戻り値
+/// * `Ok(T)` - 読み込んだ内容（ファイルが存在しない場合は既定値）
+/// * `Err(anyhow::Error)` - ファイルの読み込み・解析に失敗した場合
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// 内容を JSON ファイルに書き出す関数（一時ファイルに書いてから置き換える）
+/// # 引数
+/// * `path` - 書き出す JSON ファイルのパス
+/// * `value` - 書き出す内容
+/// # 戻り値
+/// * `Err(anyhow::Error)` - シリアライズ・書き込みに失敗した場合
+pub fn save_json_atomic<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    let json = serde_json::to_string(value)
+        .with_context(|| format!("Failed to serialize {}", path.display()))?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn saves_and_loads_json() {
+        let path = std::env::temp_dir().join(format!("kasu-persist-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // ファイルがなければ空の状態から始める
+        let empty: HashMap<u64, u32> = load_json(&path).unwrap();
+        assert!(empty.is_empty());
+
+        let saved = HashMap::from([(1_u64, 2_u32)]);
+        save_json_atomic(&path, &saved).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        assert_eq!(load_json::<HashMap<u64, u32>>(&path).unwrap(), saved);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(load_json::<HashMap<u64, u32>>(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn flushes_only_after_changes() {
+        let path = std::env::temp_dir().join(format!("kasu-flush-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let file = JsonFile::new(&path);
+
+        // 変更がなければ書き出さない
+        file.flush(|| -> Vec<u32> { panic!("snapshot without changes") })
+            .unwrap();
+        assert!(!path.exists());
+
+        file.mark_dirty();
+        file.flush(|| vec![1_u32]).unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path).unwrap(), vec![1]);
+        file.flush(|| -> Vec<u32> { panic!("snapshot without changes") })
+            .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,404 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serenity::async_trait;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// プラットフォームに依存しない受信メッセージを表す構造体
+/// # フィールド
+/// * `id` - メッセージID
+/// * `channel_id` - 送信先チャンネルID
+/// * `guild_id` - 送信先サーバーID（DMの場合は `None`）
+/// * `category_id` - 送信先チャンネルが属するカテゴリID（不明な場合は `None`）
+/// * `author_id` - 送信者のユーザーID
+/// * `author_name` - 送信者のユーザー名
+/// * `author_bot` - 送信者がボットかどうか
+/// * `role_ids` - 送信者がサーバーで持つロールID
+/// * `content` - メッセージ本文
+/// * `stickers` - メッセージに付いているスタンプの名前
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IncomingMessage {
+    pub id: u64,
+    pub channel_id: u64,
+    pub guild_id: Option<u64>,
+    pub category_id: Option<u64>,
+    pub author_id: u64,
+    pub author_name: String,
+    pub author_bot: bool,
+    pub role_ids: Vec<u64>,
+    pub content: String,
+    pub stickers: Vec<String>,
+}
+
+/// リアクションに使う絵文字
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Emoji {
+    /// サーバーのカスタム絵文字（名前で指定。例: `"kasu"`）
+    Custom(String),
+    /// Unicode 絵文字（DM などカスタム絵文字が使えない場所向け。例: `"⚔️"`）
+    Unicode(String),
+}
+
+impl std::fmt::Display for Emoji {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Emoji::Custom(name) => write!(f, ":{}:", name),
+            Emoji::Unicode(emoji) => write!(f, "{}", emoji),
+        }
+    }
+}
+
+/// 返信に使う埋め込み
+/// # フィールド
+/// * `title` - タイトル
+/// * `description` - 本文
+/// * `color` - 左端の色（`0xRRGGBB`）
+/// * `footer` - フッターの文言
+/// * `thumbnail_url` - 右上に表示する小さな画像のURL
+/// * `image_url` - 下部に表示する画像のURL
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Embed {
+    pub title: String,
+    pub description: String,
+    pub color: u32,
+    pub footer: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub image_url: Option<String>,
+}
+
+/// メッセージに付けるボタン
+/// # フィールド
+/// * `custom_id` - 押されたときに通知される識別子
+/// * `label` - ボタンに表示する文言
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Button {
+    pub custom_id: String,
+    pub label: String,
+}
+
+/// ボタンが押されたことを表す構造体
+/// # フィールド
+/// * `custom_id` - 押されたボタンの識別子
+/// * `message_id` - ボタンが付いているメッセージID
+/// * `channel_id` - ボタンが付いているメッセージのチャンネルID
+/// * `guild_id` - サーバーID（DMの場合は `None`）
+/// * `user_id` - ボタンを押したユーザーID
+/// * `user_name` - ボタンを押したユーザー名
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComponentPress {
+    pub custom_id: String,
+    pub message_id: u64,
+    pub channel_id: u64,
+    pub guild_id: Option<u64>,
+    pub user_id: u64,
+    pub user_name: String,
+}
+
+/// スラッシュコマンドの呼び出しを表す構造体
+/// # フィールド
+/// * `name` - コマンド名（例: `"samurai"`）
+/// * `subcommand` - サブコマンド名（例: `"collection"`）
+/// * `options` - 文字列オプションの名前と値の組（例: `("period", "weekly")`）
+/// * `channel_id` - 呼び出されたチャンネルID
+/// * `guild_id` - サーバーID（DMの場合は `None`）
+/// * `user_id` - 呼び出したユーザーID
+/// * `user_name` - 呼び出したユーザー名
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandInvocation {
+    pub name: String,
+    pub subcommand: Option<String>,
+    pub options: Vec<(String, String)>,
+    pub channel_id: u64,
+    pub guild_id: Option<u64>,
+    pub user_id: u64,
+    pub user_name: String,
+}
+
+impl CommandInvocation {
+    /// 指定した名前の文字列オプションの値を返す関数
+    /// # 引数
+    /// * `name` - オプション名
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// 送信済みメッセージを指し示す構造体
+/// # フィールド
+/// * `channel_id` - メッセージが存在するチャンネルID
+/// * `message_id` - メッセージID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MessageRef {
+    pub channel_id: u64,
+    pub message_id: u64,
+}
+
+/// チャットプラットフォーム（Discord など）への副作用をまとめたトレイト
+///
+/// ハンドラはこのトレイト越しにリアクションや返信を行うため、
+/// テストではゲートウェイに接続せずに記録用の実装へ差し替えられます。
+#[async_trait]
+pub trait ChatPlatform: Send + Sync {
+    /// メッセージに絵文字でリアクションする
+    /// # 引数
+    /// * `msg` - リアクション対象のメッセージ
+    /// * `emoji` - リアクションに使う絵文字
+    async fn react(&self, msg: &IncomingMessage, emoji: &Emoji) -> Result<()>;
+
+    /// メッセージに返信する
+    /// # 引数
+    /// * `msg` - 返信対象のメッセージ
+    /// * `content` - 返信内容
+    /// # 戻り値
+    /// * `Ok(MessageRef)` - 送信した返信メッセージ
+    async fn reply(&self, msg: &IncomingMessage, content: &str) -> Result<MessageRef>;
+
+    /// メッセージに埋め込みで返信する
+    /// # 引数
+    /// * `msg` - 返信対象のメッセージ
+    /// * `embed` - 返信する埋め込み
+    /// * `buttons` - 返信に付けるボタン（空なら付けない）
+    /// # 戻り値
+    /// * `Ok(MessageRef)` - 送信した返信メッセージ
+    async fn reply_embed(
+        &self,
+        msg: &IncomingMessage,
+        embed: &Embed,
+        buttons: &[Button],
+    ) -> Result<MessageRef>;
+
+    /// チャンネルに埋め込みを投稿する（返信ではない）
+    /// # 引数
+    /// * `channel_id` - 投稿先のチャンネルID
+    /// * `embed` - 投稿する埋め込み
+    /// # 戻り値
+    /// * `Ok(MessageRef)` - 投稿したメッセージ
+    async fn send_embed(&self, channel_id: u64, embed: &Embed) -> Result<MessageRef>;
+
+    /// 送信済みのメッセージを編集する（埋め込みは取り除かれる）
+    /// # 引数
+    /// * `target` - 編集対象のメッセージ
+    /// * `content` - 新しい内容
+    async fn edit(&self, target: MessageRef, content: &str) -> Result<()>;
+
+    /// 送信済みのメッセージを削除する
+    /// # 引数
+    /// * `target` - 削除対象のメッセージ
+    async fn delete(&self, target: MessageRef) -> Result<()>;
+
+    /// チャンネルに入力中インジケーターを表示する
+    /// # 引数
+    /// * `channel_id` - 対象チャンネルID
+    async fn typing(&self, channel_id: u64) -> Result<()>;
+}
+
+/// ボタンの押下やスラッシュコマンド（インタラクション）への応答をまとめたトレイト
+///
+/// 1つのインタラクションにつき、いずれか1回だけ呼び出します。
+#[async_trait]
+pub trait InteractionResponder: Send + Sync {
+    /// ボタンが付いているメッセージの埋め込みとボタンを差し替える
+    /// # 引数
+    /// * `embed` - 新しい埋め込み
+    /// * `buttons` - 新しいボタン
+    async fn update_embed(&self, embed: &Embed, buttons: &[Button]) -> Result<()>;
+
+    /// 埋め込みのメッセージで応答する
+    /// # 引数
+    /// * `embed` - 応答する埋め込み
+    async fn respond_embed(&self, embed: &Embed) -> Result<()>;
+
+    /// ボタンを押したユーザーにだけ見えるメッセージで応答する
+    /// # 引数
+    /// * `content` - 応答内容
+    async fn respond_ephemeral(&self, content: &str) -> Result<()>;
+}
+
+/// `RecordingPlatform` が記録する操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedAction {
+    React {
+        message_id: u64,
+        emoji: Emoji,
+    },
+    Reply {
+        message_id: u64,
+        reply: MessageRef,
+        content: String,
+    },
+    ReplyEmbed {
+        message_id: u64,
+        reply: MessageRef,
+        embed: Embed,
+        buttons: Vec<Button>,
+    },
+    SendEmbed {
+        channel_id: u64,
+        embed: Embed,
+    },
+    UpdateEmbed {
+        embed: Embed,
+        buttons: Vec<Button>,
+    },
+    RespondEmbed {
+        embed: Embed,
+    },
+    Ephemeral {
+        content: String,
+    },
+    Edit {
+        target: MessageRef,
+        content: String,
+    },
+    Delete {
+        target: MessageRef,
+    },
+    Typing {
+        channel_id: u64,
+    },
+}
+
+/// 実際には送信せず、行われた操作をメモリ上に記録する `ChatPlatform` の実装
+#[derive(Debug, Default)]
+pub struct RecordingPlatform {
+    actions: Mutex<Vec<RecordedAction>>,
+    next_message_id: AtomicU64,
+}
+
+impl RecordingPlatform {
+    /// 新しい RecordingPlatform を作成する関数
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// これまでに記録された操作を取得する関数
+    /// # 戻り値
+    /// * `Vec<RecordedAction>` - 記録順の操作一覧
+    #[cfg(test)]
+    pub fn actions(&self) -> Vec<RecordedAction> {
+        self.actions
+            .lock()
+            .map(|actions| actions.clone())
+            .unwrap_or_default()
+    }
+
+    /// 記録された操作を取り出し、記録をクリアする関数
+    /// # 戻り値
+    /// * `Vec<RecordedAction>` - 記録順の操作一覧
+    pub fn take_actions(&self) -> Vec<RecordedAction> {
+        self.actions
+            .lock()
+            .map(|mut actions| std::mem::take(&mut *actions))
+            .unwrap_or_default()
+    }
+
+    /// 返信IDは記録用に連番で払い出す
+    fn next_reply(&self, msg: &IncomingMessage) -> MessageRef {
+        self.next_message(msg.channel_id)
+    }
+
+    /// 送信したメッセージのIDを記録用に連番で払い出す
+    fn next_message(&self, channel_id: u64) -> MessageRef {
+        MessageRef {
+            channel_id,
+            message_id: self.next_message_id.fetch_add(1, Ordering::Relaxed) + 1,
+        }
+    }
+
+    fn record(&self, action: RecordedAction) {
+        if let Ok(mut actions) = self.actions.lock() {
+            actions.push(action);
+        }
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for RecordingPlatform {
+    async fn react(&self, msg: &IncomingMessage, emoji: &Emoji) -> Result<()> {
+        self.record(RecordedAction::React {
+            message_id: msg.id,
+            emoji: emoji.clone(),
+        });
+        Ok(())
+    }
+
+    async fn reply(&self, msg: &IncomingMessage, content: &str) -> Result<MessageRef> {
+        let reply = self.next_reply(msg);
+        self.record(RecordedAction::Reply {
+            message_id: msg.id,
+            reply,
+            content: content.to_string(),
+        });
+        Ok(reply)
+    }
+
+    async fn reply_embed(
+        &self,
+        msg: &IncomingMessage,
+        embed: &Embed,
+        buttons: &[Button],
+    ) -> Result<MessageRef> {
+        let reply = self.next_reply(msg);
+        self.record(RecordedAction::ReplyEmbed {
+            message_id: msg.id,
+            reply,
+            embed: embed.clone(),
+            buttons: buttons.to_vec(),
+        });
+        Ok(reply)
+    }
+
+    async fn send_embed(&self, channel_id: u64, embed: &Embed) -> Result<MessageRef> {
+        self.record(RecordedAction::SendEmbed {
+            channel_id,
+            embed: embed.clone(),
+        });
+        Ok(self.next_message(channel_id))
+    }
+
+    async fn edit(&self, target: MessageRef, content: &str) -> Result<()> {
+        self.record(RecordedAction::Edit {
+            target,
+            content: content.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn delete(&self, target: MessageRef) -> Result<()> {
+        self.record(RecordedAction::Delete { target });
+        Ok(())
+    }
+
+    async fn typing(&self, channel_id: u64) -> Result<()> {
+        self.record(RecordedAction::Typing { channel_id });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl InteractionResponder for RecordingPlatform {
+    async fn update_embed(&self, embed: &Embed, buttons: &[Button]) -> Result<()> {
+        self.record(RecordedAction::UpdateEmbed {
+            embed: embed.clone(),
+            buttons: buttons.to_vec(),
+        });
+        Ok(())
+    }
+
+    async fn respond_embed(&self, embed: &Embed) -> Result<()> {
+        self.record(RecordedAction::RespondEmbed {
+            embed: embed.clone(),
+        });
+        Ok(())
+    }
+
+    async fn respond_ephemeral(&self, content: &str) -> Result<()> {
+        self.record(RecordedAction::Ephemeral {
+            content: content.to_string(),
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::sync::{Mutex, PoisonError};
+
+/// ボット全体で共有する乱数生成器
+///
+/// シードを指定して作成すると、同じ入力に対して常に同じ結果になるため、
+/// テストや不具合の調査で侍の抽選・確率の判定を再現できます。
+/// `StdRng` はバージョンによってアルゴリズムが変わりうるため、出力が固定された `ChaCha8Rng` を使います。
+pub struct SharedRng {
+    rng: Mutex<ChaCha8Rng>,
+}
+
+impl SharedRng {
+    /// OS の乱数でシードした SharedRng を作成する関数
+    pub fn from_os_rng() -> Self {
+        Self {
+            rng: Mutex::new(ChaCha8Rng::from_os_rng()),
+        }
+    }
+
+    /// 指定したシードの SharedRng を作成する関数
+    /// # 引数
+    /// * `seed` - 乱数のシード
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(ChaCha8Rng::seed_from_u64(seed)),
+        }
+    }
+
+    /// 乱数生成器を借りて処理を行う関数
+    /// # 引数
+    /// * `f` - 乱数生成器を使う処理
+    pub fn with<T>(&self, f: impl FnOnce(&mut ChaCha8Rng) -> T) -> T {
+        let mut rng = self.rng.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut rng)
+    }
+}
@@ -0,0 +1,165 @@
+use crate::handler::{self, HandlerDeps};
+use crate::platform::{IncomingMessage, RecordedAction, RecordingPlatform};
+use anyhow::{Context, Result};
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// REPL で入力したメッセージに付与する疑似的なID
+/// （DM 扱いにならないよう guild_id も埋めておく）
+const REPL_CHANNEL_ID: u64 = 1;
+const REPL_GUILD_ID: u64 = 1;
+const REPL_AUTHOR_ID: u64 = 1;
+const REPL_AUTHOR_NAME: &str = "repl";
+
+/// 標準入力から1行ずつ読み込み、Discord と同じ判定・応答処理を実行する関数
+///
+/// 実際には送信せず、送られるはずだったリアクションと返信を標準出力に表示します。
+/// 空行は無視し、`:quit` または EOF で終了します。
+/// # 引数
+/// * `deps` - メッセージ処理に必要な依存
+pub async fn run(deps: &HandlerDeps) -> Result<()> {
+    let platform = RecordingPlatform::new();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut next_id: u64 = 1;
+
+    print_prompt()?;
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .context("Failed to read line from stdin")?
+    {
+        let content = line.trim_end();
+        if content == ":quit" {
+            break;
+        }
+        if !content.is_empty() {
+            let msg = IncomingMessage {
+                id: next_id,
+                channel_id: REPL_CHANNEL_ID,
+                guild_id: Some(REPL_GUILD_ID),
+                author_id: REPL_AUTHOR_ID,
+                author_name: REPL_AUTHOR_NAME.to_string(),
+                author_bot: false,
+                content: content.to_string(),
+                ..Default::default()
+            };
+            next_id += 1;
+
+            handler::handle_message(deps, &platform, &msg).await;
+
+            let actions = platform.take_actions();
+            let mut stdout = std::io::stdout().lock();
+            if actions.is_empty() {
+                writeln!(stdout, "(no trigger)")?;
+            }
+            for action in &actions {
+                writeln!(stdout, "{}", format_action(action))?;
+            }
+        }
+        print_prompt()?;
+    }
+    Ok(())
+}
+
+/// プロンプトを表示する関数
+fn print_prompt() -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    write!(stdout, "> ")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// 記録された操作を表示用の文字列に変換する関数
+/// # 引数
+/// * `action` - 記録された操作
+/// # 戻り値
+/// * `String` - 表示用の文字列
+fn format_action(action: &RecordedAction) -> String {
+    match action {
+        RecordedAction::React { emoji, .. } => format!("[reaction] {}", emoji),
+        RecordedAction::Reply { content, .. } => format!("[reply] {}", content),
+        RecordedAction::ReplyEmbed { embed, buttons, .. }
+        | RecordedAction::UpdateEmbed { embed, buttons } => {
+            let tag = if matches!(action, RecordedAction::UpdateEmbed { .. }) {
+                "update embed"
+            } else {
+                "reply embed"
+            };
+            let mut lines = vec![
+                format!("[{}] {}", tag, embed.title),
+                embed.description.clone(),
+            ];
+            lines.extend(embed.footer.clone());
+            if !buttons.is_empty() {
+                let labels: Vec<&str> = buttons.iter().map(|b| b.label.as_str()).collect();
+                lines.push(format!("[buttons] {}", labels.join(" / ")));
+            }
+            lines.join("\n")
+        }
+        RecordedAction::SendEmbed { channel_id, embed } => {
+            format!(
+                "[post {}] {}\n{}",
+                channel_id, embed.title, embed.description
+            )
+        }
+        RecordedAction::RespondEmbed { embed } => {
+            format!("[respond embed] {}\n{}", embed.title, embed.description)
+        }
+        RecordedAction::Ephemeral { content } => format!("[ephemeral] {}", content),
+        RecordedAction::Edit { target, content } => {
+            format!("[edit {}] {}", target.message_id, content)
+        }
+        RecordedAction::Delete { target } => format!("[delete {}]", target.message_id),
+        RecordedAction::Typing { .. } => "[typing]".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::{Button, Embed, Emoji, MessageRef};
+
+    #[test]
+    fn formats_recorded_actions() {
+        let reaction = RecordedAction::React {
+            message_id: 1,
+            emoji: Emoji::Custom("kasu".to_string()),
+        };
+        let reply = RecordedAction::Reply {
+            message_id: 1,
+            reply: MessageRef {
+                channel_id: 1,
+                message_id: 2,
+            },
+            content: "0: テスト侍\nテスト".to_string(),
+        };
+
+        assert_eq!(format_action(&reaction), "[reaction] :kasu:");
+        assert_eq!(format_action(&reply), "[reply] 0: テスト侍\nテスト");
+        let embed = RecordedAction::ReplyEmbed {
+            message_id: 1,
+            reply: MessageRef {
+                channel_id: 1,
+                message_id: 3,
+            },
+            embed: Embed {
+                title: "テスト侍".to_string(),
+                description: "テスト".to_string(),
+                footer: Some("ID: 0".to_string()),
+                ..Default::default()
+            },
+            buttons: vec![Button {
+                custom_id: "samurai:detail:0:1:0".to_string(),
+                label: "詳細".to_string(),
+            }],
+        };
+        assert_eq!(
+            format_action(&embed),
+            "[reply embed] テスト侍\nテスト\nID: 0\n[buttons] 詳細"
+        );
+        assert_eq!(
+            format_action(&RecordedAction::Typing { channel_id: 1 }),
+            "[typing]"
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use crate::detect::Trigger;
+use crate::persist::{self, JsonFile};
+use crate::platform::MessageRef;
+use anyhow::Result;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// 追跡する元メッセージ数の上限（超えたら古いものから忘れる）
+const MAX_TRACKED: usize = 1_000;
+
+/// 元メッセージに対してボットが行った応答
+/// # フィールド
+/// * `trigger` - 応答したトリガー（複数のトリガーを実行した場合は最初のもの）
+/// * `replies` - 送信した返信（リアクションのみの場合や送信前は空）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackedReply {
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub replies: Vec<MessageRef>,
+}
+
+/// 永続化ファイルに保存する1件分の対応
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedReply {
+    source_id: u64,
+    #[serde(flatten)]
+    tracked: TrackedReply,
+}
+
+/// 元メッセージIDとボットの返信の対応を保持する構造体
+///
+/// 同じメッセージへの重複応答を防ぎ、元メッセージの編集・削除時に返信を特定するために使います。
+/// 応答生成中（Ollama への問い合わせ中）のメッセージは、削除時に問い合わせを取り消せるよう別に保持します。
+#[derive(Debug, Default)]
+pub struct ReplyTracker {
+    inner: Mutex<TrackerInner>,
+    in_flight: Mutex<HashMap<u64, CancellationToken>>,
+    file: Option<JsonFile>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerInner {
+    entries: HashMap<u64, TrackedReply>,
+    order: VecDeque<u64>,
+}
+
+impl TrackerInner {
+    /// 追跡を追加し、上限を超えた古いものを捨てる
+    fn insert(&mut self, source_id: u64, tracked: TrackedReply) {
+        if self.entries.insert(source_id, tracked).is_none() {
+            self.order.push_back(source_id);
+        }
+        while self.order.len() > MAX_TRACKED {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// ファイルに書き出す形式に変換する（古い順）
+    fn persisted(&self) -> Vec<PersistedReply> {
+        self.order
+            .iter()
+            .filter_map(|id| {
+                self.entries.get(id).map(|tracked| PersistedReply {
+                    source_id: *id,
+                    tracked: tracked.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl ReplyTracker {
+    /// 新しい ReplyTracker を作成する関数（メモリ上のみ）
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ファイルに永続化する ReplyTracker を作成する関数
+    ///
+    /// ファイルが存在すれば前回の対応を読み込みます（存在しなければ空の状態から始めます）。
+    /// # 引数
+    /// * `path` - 永続化先の JSON ファイルのパス
+    /// # 戻り値
+    /// * `Ok(ReplyTracker)` - 読み込みに成功した場合
+    /// * `Err(anyhow::Error)` - ファイルの読み込み・解析に失敗した場合
+    pub fn with_persistence(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut inner = TrackerInner::default();
+        let persisted: Vec<PersistedReply> = persist::load_json(path)?;
+        for entry in persisted {
+            inner.insert(entry.source_id, entry.tracked);
+        }
+        Ok(Self {
+            inner: Mutex::new(inner),
+            in_flight: Mutex::default(),
+            file: Some(JsonFile::new(path)),
+        })
+    }
+
+    /// 元メッセージへの応答を開始する関数
+    /// # 引数
+    /// * `source_id` - 元メッセージID
+    /// * `trigger` - 応答するトリガー
+    /// # 戻り値
+    /// * `true` - 未応答のメッセージで、応答を開始してよい場合
+    /// * `false` - すでに応答済み（または応答中）の場合
+    pub fn try_claim(&self, source_id: u64, trigger: Trigger) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        if inner.entries.contains_key(&source_id) {
+            return false;
+        }
+        inner.insert(
+            source_id,
+            TrackedReply {
+                trigger,
+                replies: Vec::new(),
+            },
+        );
+        true
+    }
+
+    /// 送信した返信を記録する関数（1つの元メッセージに複数の返信を記録できる）
+    /// # 引数
+    /// * `source_id` - 元メッセージID
+    /// * `reply` - 送信した返信
+    /// # 戻り値
+    /// * `true` - 記録した場合
+    /// * `false` - 応答中に元メッセージの追跡が外れた（削除された）場合
+    pub fn add_reply(&self, source_id: u64, reply: MessageRef) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        let Some(entry) = inner.entries.get_mut(&source_id) else {
+            return false;
+        };
+        entry.replies.push(reply);
+        self.mark_dirty();
+        true
+    }
+
+    /// 元メッセージへの応答を取得する関数
+    /// # 引数
+    /// * `source_id` - 元メッセージID
+    pub fn get(&self, source_id: u64) -> Option<TrackedReply> {
+        self.inner
+            .lock()
+            .ok()
+            .and_then(|inner| inner.entries.get(&source_id).cloned())
+    }
+
+    /// 元メッセージの追跡をやめる関数
+    /// # 引数
+    /// * `source_id` - 元メッセージID
+    /// # 戻り値
+    /// * `Option<TrackedReply>` - 追跡していた応答
+    pub fn remove(&self, source_id: u64) -> Option<TrackedReply> {
+        let mut inner = self.inner.lock().ok()?;
+        let removed = inner.entries.remove(&source_id)?;
+        inner.order.retain(|id| *id != source_id);
+        self.mark_dirty();
+        Some(removed)
+    }
+
+    /// 元メッセージへの応答生成を開始したことを記録する関数
+    /// # 引数
+    /// * `source_id` - 元メッセージID
+    /// # 戻り値
+    /// * `CancellationToken` - 元メッセージが削除されたときに取り消されるトークン
+    pub fn begin_request(&self, source_id: u64) -> CancellationToken {
+        let token = CancellationToken::new();
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.insert(source_id, token.clone());
+        }
+        token
+    }
+
+    /// 元メッセージへの応答生成が終わったことを記録する関数
+    /// # 引数
+    /// * `source_id` - 元メッセージID
+    pub fn finish_request(&self, source_id: u64) {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.remove(&source_id);
+        }
+    }
+
+    /// 元メッセージへの応答生成を取り消す関数
+    /// # 引数
+    /// * `source_id` - 元メッセージID
+    /// # 戻り値
+    /// * `true` - 応答生成中で、取り消した場合
+    pub fn cancel_request(&self, source_id: u64) -> bool {
+        let token = self
+            .in_flight
+            .lock()
+            .ok()
+            .and_then(|mut in_flight| in_flight.remove(&source_id));
+        match token {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 変更があったことを記録する（書き出しは `flush` で行う）
+    fn mark_dirty(&self) {
+        if let Some(file) = &self.file {
+            file.mark_dirty();
+        }
+    }
+
+    /// 変更があれば、現在の対応をファイルに書き出す関数（ブロッキング I/O を行う）
+    pub fn flush(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let snapshot = || {
+            self.inner
+                .lock()
+                .map(|inner| inner.persisted())
+                .unwrap_or_default()
+        };
+        if let Err(e) = file.flush(snapshot) {
+            error!("{:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY: MessageRef = MessageRef {
+        channel_id: 1,
+        message_id: 100,
+    };
+    const SECOND_REPLY: MessageRef = MessageRef {
+        channel_id: 1,
+        message_id: 101,
+    };
+
+    #[test]
+    fn claims_each_source_once() {
+        let tracker = ReplyTracker::new();
+        assert!(tracker.try_claim(1, Trigger::Samurai));
+        assert!(!tracker.try_claim(1, Trigger::Zaurus));
+        assert!(tracker.try_claim(2, Trigger::Zaurus));
+
+        assert!(tracker.add_reply(1, REPLY));
+        assert!(tracker.add_reply(1, SECOND_REPLY));
+        assert!(!tracker.add_reply(3, REPLY));
+        assert_eq!(
+            tracker.get(1),
+            Some(TrackedReply {
+                trigger: Trigger::Samurai,
+                replies: vec![REPLY, SECOND_REPLY],
+            })
+        );
+        assert!(tracker.get(2).unwrap().replies.is_empty());
+    }
+
+    #[test]
+    fn remove_allows_reclaim() {
+        let tracker = ReplyTracker::new();
+        assert!(tracker.try_claim(1, Trigger::Samurai));
+        assert!(tracker.remove(1).is_some());
+        assert!(tracker.remove(1).is_none());
+        assert!(tracker.try_claim(1, Trigger::Samurai));
+    }
+
+    #[test]
+    fn forgets_oldest_entries_beyond_capacity() {
+        let tracker = ReplyTracker::new();
+        for id in 0..(MAX_TRACKED as u64 + 1) {
+            assert!(tracker.try_claim(id, Trigger::Samurai));
+        }
+        assert!(tracker.get(0).is_none());
+        assert!(tracker.get(1).is_some());
+    }
+
+    #[test]
+    fn cancels_in_flight_requests() {
+        let tracker = ReplyTracker::new();
+        let token = tracker.begin_request(1);
+        assert!(tracker.cancel_request(1));
+        assert!(token.is_cancelled());
+        assert!(!tracker.cancel_request(1));
+
+        tracker.begin_request(2);
+        tracker.finish_request(2);
+        assert!(!tracker.cancel_request(2));
+    }
+
+    #[test]
+    fn persists_replies_across_restarts() {
+        let path = std::env::temp_dir().join(format!("kasu-replies-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let tracker = ReplyTracker::with_persistence(&path).unwrap();
+        assert!(tracker.try_claim(1, Trigger::Samurai));
+        assert!(tracker.add_reply(1, REPLY));
+        assert!(tracker.try_claim(2, Trigger::Zaurus));
+        assert!(tracker.add_reply(2, REPLY));
+        tracker.remove(2);
+        // 書き出すまではファイルに反映しない
+        assert!(!path.exists());
+        tracker.flush();
+
+        let restored = ReplyTracker::with_persistence(&path).unwrap();
+        assert_eq!(restored.get(1).unwrap().replies, vec![REPLY]);
+        assert!(restored.get(2).is_none());
+        assert!(!restored.try_claim(1, Trigger::Samurai));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use crate::health::HealthState;
+use crate::metrics::METRICS;
+use anyhow::{Context, Result};
+use log::{debug, error, info};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// リクエストヘッダーとして読み込む最大バイト数
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// HTTP レスポンスを表す構造体
+/// # フィールド
+/// * `status` - ステータスコードと理由句（例: `"200 OK"`）
+/// * `content_type` - Content-Type ヘッダー
+/// * `body` - レスポンスボディ
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: &'static str, body: String) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    fn not_found() -> Self {
+        Self::json("404 Not Found", r#"{"error":"not found"}"#.to_string())
+    }
+}
+
+/// 稼働状態を公開するローカル HTTP サーバーを起動する関数
+///
+/// `GET /healthz` は常に 200 を、`GET /readyz` は応答可能なときだけ 200（それ以外は 503）を返します。
+/// `GET /metrics` は Prometheus のテキスト形式でメトリクスを返します。
+/// # 引数
+/// * `addr` - 待ち受けアドレス（例: `"127.0.0.1:8080"`）
+/// * `health` - 公開する稼働状態
+pub async fn serve(addr: &str, health: Arc<HealthState>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind health server to {}", addr))?;
+    info!("Health server listening on {}", addr);
+    accept_loop(listener, health).await
+}
+
+/// 接続を受け付けてリクエストごとにタスクを起動する関数
+async fn accept_loop(listener: TcpListener, health: Arc<HealthState>) -> Result<()> {
+    loop {
+        let (stream, peer) = listener
+            .accept()
+            .await
+            .context("Failed to accept health server connection")?;
+        let health = Arc::clone(&health);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &health).await {
+                debug!("Health server connection from {} failed: {:?}", peer, e);
+            }
+        });
+    }
+}
+
+/// 1つの接続を処理する関数（1リクエストごとに接続を閉じる）
+async fn handle_connection(mut stream: TcpStream, health: &HealthState) -> Result<()> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    // ヘッダーの終端まで読み込む（ボディは扱わない）
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_BYTES {
+        let n = stream
+            .read(&mut chunk)
+            .await
+            .context("Failed to read request")?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&buf);
+    let response = match parse_request_line(&request) {
+        Some(("GET", path)) => route(path, health),
+        Some(_) => Response::json(
+            "405 Method Not Allowed",
+            r#"{"error":"method not allowed"}"#.to_string(),
+        ),
+        None => Response::json("400 Bad Request", r#"{"error":"bad request"}"#.to_string()),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream
+        .write_all(head.as_bytes())
+        .await
+        .context("Failed to write response head")?;
+    stream
+        .write_all(response.body.as_bytes())
+        .await
+        .context("Failed to write response body")?;
+    stream.shutdown().await.ok();
+    Ok(())
+}
+
+/// リクエストラインからメソッドとパス（クエリ文字列を除く）を取り出す関数
+/// # 引数
+/// * `request` - 受信したリクエスト
+/// # 戻り値
+/// * `Some((method, path))` - 解析できた場合
+/// * `None` - リクエストラインが不正な場合
+fn parse_request_line(request: &str) -> Option<(&str, &str)> {
+    let line = request.lines().next()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    parts.next()?.starts_with("HTTP/").then_some(())?;
+    let path = target.split('?').next().unwrap_or(target);
+    Some((method, path))
+}
+
+/// パスに応じてレスポンスを組み立てる関数
+/// # 引数
+/// * `path` - リクエストパス
+/// * `health` - 稼働状態
+fn route(path: &str, health: &HealthState) -> Response {
+    match path {
+        "/healthz" => Response::json("200 OK", health.details().to_string()),
+        "/readyz" => {
+            let status = if health.is_ready() {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            Response::json(status, health.details().to_string())
+        }
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4",
+            body: METRICS.render(),
+        },
+        _ => Response::not_found(),
+    }
+}
+
+/// HTTP サーバーをバックグラウンドで起動する関数（起動に失敗してもボットは継続する）
+/// # 引数
+/// * `addr` - 待ち受けアドレス
+/// * `health` - 公開する稼働状態
+pub fn spawn(addr: String, health: Arc<HealthState>) {
+    tokio::spawn(async move {
+        if let Err(e) = serve(&addr, health).await {
+            error!("Health server error: {:?}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_request_line() {
+        assert_eq!(
+            parse_request_line("GET /readyz?verbose=1 HTTP/1.1\r\nHost: x\r\n\r\n"),
+            Some(("GET", "/readyz"))
+        );
+        assert_eq!(parse_request_line("GET /healthz\r\n\r\n"), None);
+        assert_eq!(parse_request_line(""), None);
+    }
+
+    #[test]
+    fn readyz_reflects_readiness() {
+        let health = HealthState::new(Ok(1));
+        assert_eq!(route("/readyz", &health).status, "503 Service Unavailable");
+        assert_eq!(route("/healthz", &health).status, "200 OK");
+
+        health.set_gateway_connected(true);
+        assert_eq!(route("/readyz", &health).status, "200 OK");
+        assert_eq!(route("/unknown", &health).status, "404 Not Found");
+    }
+
+    #[test]
+    fn metrics_route_uses_prometheus_text_format() {
+        let health = HealthState::new(Ok(1));
+        let response = route("/metrics", &health);
+        assert_eq!(response.status, "200 OK");
+        assert_eq!(response.content_type, "text/plain; version=0.0.4");
+        assert!(
+            response
+                .body
+                .contains("# TYPE kasu_messages_seen_total counter")
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_healthz_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let health = Arc::new(HealthState::new(Ok(2)));
+        tokio::spawn(accept_loop(listener, health));
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .expect("write");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .expect("read response");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(r#""entries":2"#));
+    }
+}
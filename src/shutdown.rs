@@ -0,0 +1,162 @@
+use log::info;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// グレースフルシャットダウンの状態を管理する構造体
+///
+/// 処理中のメッセージ数を数え、シャットダウン開始後は新しいトリガーの受け付けを止めます。
+#[derive(Debug, Default)]
+pub struct Shutdown {
+    shutting_down: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+/// 処理中であることを表すガード（ドロップ時に処理中カウントを減らす）
+#[derive(Debug)]
+pub struct InFlightGuard<'a> {
+    shutdown: &'a Shutdown,
+}
+
+impl Shutdown {
+    /// 新しい Shutdown を作成する関数
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// メッセージの処理を開始する関数
+    /// # 戻り値
+    /// * `Some(InFlightGuard)` - 処理を開始してよい場合
+    /// * `None` - シャットダウン中で新しい処理を受け付けない場合
+    pub fn try_begin(&self) -> Option<InFlightGuard<'_>> {
+        // 先にカウントしてからフラグを確認し、シャットダウン開始との競合で取りこぼさないようにする
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlightGuard { shutdown: self };
+        if self.is_shutting_down() {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// シャットダウン中かどうかを返す関数
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// シャットダウンを開始し、新しい処理の受け付けを止める関数
+    pub fn begin(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    /// 処理中のメッセージ数を返す関数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// 処理中のメッセージがなくなるまで待つ関数
+    /// # 引数
+    /// * `timeout` - 待機する最大時間
+    /// # 戻り値
+    /// * `true` - タイムアウト前にすべての処理が完了した場合
+    /// * `false` - タイムアウトした場合
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let wait = async {
+            loop {
+                // 通知を先に登録してからカウントを確認し、通知の取りこぼしを防ぐ
+                let mut notified = pin!(self.idle.notified());
+                notified.as_mut().enable();
+                let remaining = self.in_flight();
+                if remaining == 0 {
+                    return;
+                }
+                info!("Waiting for {} in-flight message(s)", remaining);
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.is_ok()
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if self.shutdown.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shutdown.idle.notify_waiters();
+        }
+    }
+}
+
+/// SIGINT (Ctrl-C) または SIGTERM を受信するまで待つ関数
+pub async fn wait_for_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for Ctrl-C: {:?}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                log::error!("Failed to listen for SIGTERM: {:?}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn rejects_new_work_after_begin() {
+        let shutdown = Shutdown::new();
+        assert!(shutdown.try_begin().is_some());
+
+        shutdown.begin();
+        assert!(shutdown.try_begin().is_none());
+        assert_eq!(shutdown.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn wait_idle_returns_when_guards_drop() {
+        let shutdown = Arc::new(Shutdown::new());
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let worker = {
+            let shutdown = Arc::clone(&shutdown);
+            tokio::spawn(async move {
+                let _guard = shutdown.try_begin().expect("accepting");
+                let _ = started_tx.send(());
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            })
+        };
+        started_rx.await.expect("worker started");
+
+        shutdown.begin();
+        assert!(shutdown.wait_idle(Duration::from_secs(5)).await);
+        worker.await.expect("worker");
+    }
+
+    #[tokio::test]
+    async fn wait_idle_times_out_while_busy() {
+        let shutdown = Shutdown::new();
+        let _guard = shutdown.try_begin().expect("accepting");
+
+        shutdown.begin();
+        assert!(!shutdown.wait_idle(Duration::from_millis(20)).await);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::detect::Trigger;
+use crate::persist::{self, JsonFile};
+use anyhow::Result;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 日本標準時（UTC+9）のオフセット（秒）
+pub const JST_OFFSET_SECS: u64 = 9 * 60 * 60;
+/// 1日の秒数
+pub const SECS_PER_DAY: u64 = 24 * 60 * 60;
+/// 日別の回数を残す日数（今週の集計に必要な分だけ）
+const RETAINED_DAYS: u32 = 7;
+
+/// ランキングの集計期間
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    /// 今日（日本時間）
+    Daily,
+    /// 今週（日本時間の月曜始まり）
+    Weekly,
+    /// 記録開始からの累計
+    AllTime,
+}
+
+impl Period {
+    /// コマンドのオプション値から集計期間を解析する関数
+    /// # 引数
+    /// * `s` - オプション値（`"daily"`・`"weekly"`・`"all"`）
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "daily" => Some(Period::Daily),
+            "weekly" => Some(Period::Weekly),
+            "all" => Some(Period::AllTime),
+            _ => None,
+        }
+    }
+
+    /// 埋め込みに表示する期間名
+    pub fn label(self) -> &'static str {
+        match self {
+            Period::Daily => "今日",
+            Period::Weekly => "今週",
+            Period::AllTime => "累計",
+        }
+    }
+}
+
+/// UNIX 時刻（秒）を日本時間の日付の通し番号（1970-01-01 が 0）に変換する関数
+pub fn jst_day(unix: u64) -> u32 {
+    ((unix + JST_OFFSET_SECS) / SECS_PER_DAY) as u32
+}
+
+/// 日付の通し番号から曜日を返す関数（月曜日が 0、日曜日が 6。1970-01-01 は木曜日）
+pub fn weekday(day: u32) -> u32 {
+    (day + 3) % 7
+}
+
+/// その日を含む週の月曜日の通し番号を返す関数
+fn week_start(day: u32) -> u32 {
+    day - weekday(day)
+}
+
+/// ユーザー1人分のトリガー回数
+/// # フィールド
+/// * `total` - トリガーごとの累計回数
+/// * `days` - 日付（通し番号）ごとのトリガー回数（直近の分のみ）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UserStats {
+    #[serde(default)]
+    total: HashMap<Trigger, u64>,
+    #[serde(default)]
+    days: BTreeMap<u32, HashMap<Trigger, u64>>,
+}
+
+impl UserStats {
+    /// 期間内の回数を返す（`trigger` が `None` の場合は全トリガーの合計）
+    fn count(&self, period: Period, trigger: Option<Trigger>, today: u32) -> u64 {
+        let sum = |counts: &HashMap<Trigger, u64>| -> u64 {
+            match trigger {
+                Some(trigger) => counts.get(&trigger).copied().unwrap_or(0),
+                None => counts.values().sum(),
+            }
+        };
+        let since = match period {
+            Period::AllTime => return sum(&self.total),
+            Period::Daily => today,
+            Period::Weekly => week_start(today),
+        };
+        self.days.range(since..=today).map(|(_, c)| sum(c)).sum()
+    }
+}
+
+/// サーバーごと・ユーザーごとのトリガー回数を保持する構造体（ランキング用）
+///
+/// 永続化先が設定されていれば、`flush` で JSON ファイルへ書き出します。
+#[derive(Debug, Default)]
+pub struct TriggerStats {
+    guilds: Mutex<HashMap<u64, HashMap<u64, UserStats>>>,
+    file: Option<JsonFile>,
+}
+
+impl TriggerStats {
+    /// 新しい TriggerStats を作成する関数（メモリ上のみ）
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ファイルに永続化する TriggerStats を作成する関数
+    ///
+    /// ファイルが存在すれば前回の内容を読み込みます（存在しなければ空の状態から始めます）。
+    /// # 引数
+    /// * `path` - 永続化先の JSON ファイルのパス
+    /// # 戻り値
+    /// * `Ok(TriggerStats)` - 読み込みに成功した場合
+    /// * `Err(anyhow::Error)` - ファイルの読み込み・解析に失敗した場合
+    pub fn with_persistence(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Ok(Self {
+            guilds: Mutex::new(persist::load_json(path)?),
+            file: Some(JsonFile::new(path)),
+        })
+    }
+
+    /// トリガーが発生したことを記録する関数
+    /// # 引数
+    /// * `guild_id` - サーバーID
+    /// * `user_id` - トリガーを含むメッセージを送ったユーザーID
+    /// * `trigger` - 発生したトリガー
+    /// * `now` - 現在の UNIX 時刻（秒）
+    pub fn record(&self, guild_id: u64, user_id: u64, trigger: Trigger, now: u64) {
+        let Ok(mut guilds) = self.guilds.lock() else {
+            return;
+        };
+        let today = jst_day(now);
+        let user = guilds
+            .entry(guild_id)
+            .or_default()
+            .entry(user_id)
+            .or_default();
+        *user.total.entry(trigger).or_default() += 1;
+        *user
+            .days
+            .entry(today)
+            .or_default()
+            .entry(trigger)
+            .or_default() += 1;
+        // 今週の集計に使わなくなった日別の回数は捨てる
+        user.days = user
+            .days
+            .split_off(&today.saturating_sub(RETAINED_DAYS - 1));
+        self.mark_dirty();
+    }
+
+    /// サーバー内のランキングを返す関数
+    /// # 引数
+    /// * `guild_id` - サーバーID
+    /// * `period` - 集計期間
+    /// * `trigger` - 対象のトリガー（`None` の場合は全トリガーの合計）
+    /// * `now` - 現在の UNIX 時刻（秒）
+    /// * `limit` - 返す最大人数
+    /// # 戻り値
+    /// * `Vec<(u64, u64)>` - ユーザーIDと回数の組（回数の多い順、同数はユーザーIDの小さい順）
+    pub fn leaderboard(
+        &self,
+        guild_id: u64,
+        period: Period,
+        trigger: Option<Trigger>,
+        now: u64,
+        limit: usize,
+    ) -> Vec<(u64, u64)> {
+        let Ok(guilds) = self.guilds.lock() else {
+            return Vec::new();
+        };
+        let Some(users) = guilds.get(&guild_id) else {
+            return Vec::new();
+        };
+        let today = jst_day(now);
+        let mut ranking: Vec<(u64, u64)> = users
+            .iter()
+            .map(|(user_id, stats)| (*user_id, stats.count(period, trigger, today)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranking.truncate(limit);
+        ranking
+    }
+
+    /// 変更があったことを記録する（書き出しは `flush` で行う）
+    fn mark_dirty(&self) {
+        if let Some(file) = &self.file {
+            file.mark_dirty();
+        }
+    }
+
+    /// 変更があれば、現在の内容をファイルに書き出す関数（ブロッキング I/O を行う）
+    pub fn flush(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let snapshot = || {
+            self.guilds
+                .lock()
+                .map(|guilds| guilds.clone())
+                .unwrap_or_default()
+        };
+        if let Err(e) = file.flush(snapshot) {
+            error!("{:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-03（水）12:00 JST
+    const WEDNESDAY_NOON: u64 = 1_704_250_800;
+
+    #[test]
+    fn converts_unix_time_to_jst_days_and_weeks() {
+        // 2024-01-01 00:00 JST は UTC では前日の 15:00
+        let monday = jst_day(1_704_034_800);
+        assert_eq!(jst_day(1_704_034_800 - 1), monday - 1);
+        assert_eq!(jst_day(WEDNESDAY_NOON), monday + 2);
+        assert_eq!(week_start(monday), monday);
+        assert_eq!(week_start(monday + 6), monday);
+        assert_eq!(week_start(monday + 7), monday + 7);
+    }
+
+    #[test]
+    fn ranks_users_by_period() {
+        let stats = TriggerStats::new();
+        let day = SECS_PER_DAY;
+        // 先週の日曜日に 3 回
+        for _ in 0..3 {
+            stats.record(1, 100, Trigger::Samurai, WEDNESDAY_NOON - 3 * day);
+        }
+        // 今週の月曜日に 2 回、今日 1 回
+        stats.record(1, 200, Trigger::Samurai, WEDNESDAY_NOON - 2 * day);
+        stats.record(1, 200, Trigger::Zaurus, WEDNESDAY_NOON - 2 * day);
+        stats.record(1, 300, Trigger::Samurai, WEDNESDAY_NOON);
+        // 別のサーバー
+        stats.record(2, 100, Trigger::Samurai, WEDNESDAY_NOON);
+
+        let ranking = |period, trigger| stats.leaderboard(1, period, trigger, WEDNESDAY_NOON, 10);
+        assert_eq!(
+            ranking(Period::AllTime, None),
+            vec![(100, 3), (200, 2), (300, 1)]
+        );
+        assert_eq!(ranking(Period::Weekly, None), vec![(200, 2), (300, 1)]);
+        assert_eq!(
+            ranking(Period::Weekly, Some(Trigger::Samurai)),
+            vec![(200, 1), (300, 1)]
+        );
+        assert_eq!(ranking(Period::Daily, None), vec![(300, 1)]);
+        assert_eq!(
+            stats
+                .leaderboard(1, Period::AllTime, None, WEDNESDAY_NOON, 1)
+                .len(),
+            1
+        );
+        assert!(
+            stats
+                .leaderboard(3, Period::AllTime, None, WEDNESDAY_NOON, 10)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn drops_old_daily_counts_but_keeps_totals() {
+        let stats = TriggerStats::new();
+        stats.record(1, 100, Trigger::Samurai, WEDNESDAY_NOON - 30 * SECS_PER_DAY);
+        stats.record(1, 100, Trigger::Samurai, WEDNESDAY_NOON);
+
+        let guilds = stats.guilds.lock().unwrap();
+        let user = &guilds[&1][&100];
+        assert_eq!(user.days.len(), 1);
+        assert_eq!(user.total[&Trigger::Samurai], 2);
+    }
+
+    #[test]
+    fn persists_across_restarts() {
+        let path = std::env::temp_dir().join(format!("kasu-stats-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let stats = TriggerStats::with_persistence(&path).unwrap();
+        stats.record(1, 100, Trigger::Zaurus, WEDNESDAY_NOON);
+        // 書き出すまではファイルに反映しない
+        assert!(!path.exists());
+        stats.flush();
+
+        let restored = TriggerStats::with_persistence(&path).unwrap();
+        assert_eq!(
+            restored.leaderboard(1, Period::Daily, Some(Trigger::Zaurus), WEDNESDAY_NOON, 10),
+            vec![(100, 1)]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,99 +1,271 @@
-use crate::config::app_config;
-use anyhow::{Error, Ok, Result};
+use crate::config::{DatasetColumns, DatasetFormat, app_config};
+use anyhow::{Context, Error, Ok, Result, bail};
 use csv::ReaderBuilder;
 use log::{error, info};
-use rand::{Rng, rng};
-use std::fs::File;
+use rand::Rng;
+use serde_json::Value;
 use std::io::Read;
+use std::path::Path;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// JSON・TOML・YAML で侍データの配列を置くキー
+const DATASET_KEY: &str = "samurai";
 
 // データを保持するための構造体
-#[derive(Debug, Clone)]
+// id はデータ内の通し番号（0 始まり）
+// rarity / thumbnail_url / image_url はデータに列（フィールド）がある場合のみ設定される（空欄は None）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SamuraiEntry {
+    pub id: u32,
     pub name: String,
     pub description: String,
+    pub rarity: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub image_url: Option<String>,
 }
 
 /// csvのデータ数からランダムな Samurai ID を取得する関数
 /// # 引数
+/// * `rng` - 乱数生成器（シードを固定すると同じ順で選ばれる）
 /// * `idlength` - CSVファイルのデータ数
 ///
 /// # 戻り値
 /// * `u32` - ランダムな Samurai ID
-pub fn get_random_samurai_id(idlength: u32) -> u32 {
-    // ランダムな Samurai ID を生成する関数
+///
+/// シードを固定したときの並びが rand の範囲生成の実装に左右されないよう、
+/// `next_u64` の出力を乗算とシフトで範囲に縮めます（Lemire の方法、偏りは 2^-32 未満）。
+pub fn get_random_samurai_id(rng: &mut impl Rng, idlength: u32) -> u32 {
     // 0 から idlength - 1 の範囲でランダムな整数を生成
-    let mut rng = rng();
-    let number = rng.random_range(0..idlength) as u32;
-    // Samurai ID を返す
-    number as u32
+    ((u128::from(rng.next_u64()) * u128::from(idlength)) >> 64) as u32
 }
 
-/// CSVファイルを読み込んで SamuraiEntry のベクタを返す関数
+/// 侍データのファイルを読み込んで SamuraiEntry のベクタを返す関数
+///
+/// ファイル形式は `[dataset]` の `format`、省略時は拡張子から判定します。
 ///
-/// 注意: この関数は `config::app_config()` から `SAMURAI_CSV_PATH` を取得します。
+/// 注意: この関数は `config::app_config()` から侍データのパスと `[dataset]` の設定を取得します。
 /// `config::init_app_config()` が bot 起動時に呼び出されている前提で動作します。
 /// この関数は静的な Lazy 初期化で使用されるため、パラメータを受け取ることができません。
 ///
 /// # 戻り値
 /// * `Ok(Vec<SamuraiEntry>)` - 読み込んだ SamuraiEntry のベクタ
 /// * `Err(Error)` - エラーが発生した場合
-pub fn read_samurai_csv_as_vec() -> Result<Vec<SamuraiEntry>, Error> {
-    let samurai_csv_path = &app_config().samurai_csv_path;
-    let file = File::open(samurai_csv_path)?;
-    parse_samurai_reader(file)
+pub fn read_samurai_entries() -> Result<Vec<SamuraiEntry>> {
+    let config = app_config();
+    let path = Path::new(&config.samurai_data_path);
+    let format = config.dataset.format.unwrap_or_else(|| detect_format(path));
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_samurai_dataset(&text, format, &config.dataset.columns)
+}
+
+/// 拡張子からファイル形式を判定する関数（不明な拡張子は CSV とみなす）
+/// # 引数
+/// * `path` - 侍データのファイルパス
+fn detect_format(path: &Path) -> DatasetFormat {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("tsv") => DatasetFormat::Tsv,
+        Some("json") => DatasetFormat::Json,
+        Some("toml") => DatasetFormat::Toml,
+        Some("yaml" | "yml") => DatasetFormat::Yaml,
+        _ => DatasetFormat::Csv,
+    }
+}
+
+/// 侍データを解析して SamuraiEntry のベクタを返す関数
+///
+/// CSV・TSV はヘッダー行の列名、JSON・TOML・YAML は各要素のフィールド名を `columns` と照合します。
+/// JSON・YAML は要素の配列か `samurai` キーの配列、TOML は `[[samurai]]` の配列を読み込みます。
+/// # 引数
+/// * `text` - ファイルの内容
+/// * `format` - ファイル形式
+/// * `columns` - 各項目に対応する列名・フィールド名
+///
+/// # 戻り値
+/// * `Ok(Vec<SamuraiEntry>)` - 読み込んだ SamuraiEntry のベクタ
+/// * `Err(Error)` - 解析に失敗した場合、必須の列・フィールドがない場合
+pub fn parse_samurai_dataset(
+    text: &str,
+    format: DatasetFormat,
+    columns: &DatasetColumns,
+) -> Result<Vec<SamuraiEntry>> {
+    match format {
+        DatasetFormat::Csv => parse_samurai_reader(text.as_bytes(), b',', columns),
+        DatasetFormat::Tsv => parse_samurai_reader(text.as_bytes(), b'\t', columns),
+        DatasetFormat::Json => {
+            let value = serde_json::from_str(text).context("Failed to parse samurai JSON")?;
+            parse_samurai_records(value, columns)
+        }
+        DatasetFormat::Toml => {
+            let value = toml::from_str(text).context("Failed to parse samurai TOML")?;
+            parse_samurai_records(value, columns)
+        }
+        DatasetFormat::Yaml => {
+            let docs = YamlLoader::load_from_str(text).context("Failed to parse samurai YAML")?;
+            let value = docs.into_iter().next().map_or(Value::Null, yaml_to_json);
+            parse_samurai_records(value, columns)
+        }
+    }
 }
 
-/// ヘッダーを解析して SamuraiEntry のベクタを返す関数
+/// ヘッダーを解析して SamuraiEntry のベクタを返す関数（CSV・TSV）
 /// # 引数
 /// * `reader` - 読み込むリーダー
+/// * `delimiter` - 区切り文字
+/// * `columns` - 各項目に対応する列名
 ///
 /// # 戻り値
 /// * `Ok(Vec<SamuraiEntry>)` - 読み込んだ SamuraiEntry のベクタ
 /// * `Err(Error)` - エラーが発生した場合
-fn parse_samurai_reader<R: Read>(reader: R) -> Result<Vec<SamuraiEntry>, Error> {
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(reader);
+fn parse_samurai_reader<R: Read>(
+    reader: R,
+    delimiter: u8,
+    columns: &DatasetColumns,
+) -> Result<Vec<SamuraiEntry>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(delimiter)
+        .from_reader(reader);
 
     let headers = rdr.headers()?;
-    let name_index = headers.iter().position(|h| h == "Name").ok_or_else(|| {
-        Error::from(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "Failed to find 'Name' column",
-        ))
-    })?;
-    let description_index = headers
-        .iter()
-        .position(|h| h == "Description")
-        .ok_or_else(|| {
+    let position = |column: &str| headers.iter().position(|h| h == column);
+    let required = |column: &str| {
+        position(column).ok_or_else(|| {
             Error::from(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
-                "Failed to find 'Description' column",
+                format!("Failed to find '{}' column", column),
             ))
-        })?;
+        })
+    };
+    let name_index = required(&columns.name)?;
+    let description_index = required(&columns.description)?;
+
+    // 任意の列（存在しなければ None）
+    let rarity_index = position(&columns.rarity);
+    let thumbnail_index = position(&columns.thumbnail);
+    let image_index = position(&columns.image);
 
     let mut samurai_entries = Vec::new();
     for result in rdr.records() {
         let record = result?;
         let name = record.get(name_index).unwrap_or("").to_string();
         let description = record.get(description_index).unwrap_or("");
+        let optional = |index: Option<usize>| optional_value(index.and_then(|i| record.get(i)));
 
         samurai_entries.push(SamuraiEntry {
+            id: samurai_entries.len() as u32,
             name: name.to_string(),
             description: description.to_string(),
+            rarity: optional(rarity_index),
+            thumbnail_url: optional(thumbnail_index),
+            image_url: optional(image_index),
         });
     }
 
     Ok(samurai_entries)
 }
 
-/// Samurai ID に基づいて名前を取得する関数
+/// 要素の配列から SamuraiEntry のベクタを返す関数（JSON・TOML・YAML）
 /// # 引数
-/// * `df` - 読み込んだデータフレーム
+/// * `value` - 解析した侍データ（要素の配列、または `samurai` キーに配列を持つテーブル）
+/// * `columns` - 各項目に対応するフィールド名
 ///
 /// # 戻り値
-/// * `Ok(Some(name))` - Samurai ID に基づいて取得した名前と説明
-/// * `Ok(None)` - Samurai ID が見つからなかった場合
+/// * `Ok(Vec<SamuraiEntry>)` - 読み込んだ SamuraiEntry のベクタ
+/// * `Err(Error)` - 配列でない場合、必須のフィールドがない場合
+fn parse_samurai_records(value: Value, columns: &DatasetColumns) -> Result<Vec<SamuraiEntry>> {
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(mut root) => match root.remove(DATASET_KEY) {
+            Some(Value::Array(items)) => items,
+            _ => bail!("Failed to find '{}' array in samurai data", DATASET_KEY),
+        },
+        _ => bail!("Samurai data must be an array of entries"),
+    };
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let Value::Object(fields) = item else {
+                bail!("Samurai entry {} is not a table", index + 1);
+            };
+            let field = |column: &str| fields.get(column).map(value_to_string);
+            let required = |column: &str| {
+                field(column).with_context(|| {
+                    format!("Samurai entry {} has no '{}' field", index + 1, column)
+                })
+            };
+
+            Ok(SamuraiEntry {
+                id: index as u32,
+                name: required(&columns.name)?,
+                description: required(&columns.description)?,
+                rarity: optional_value(field(&columns.rarity).as_deref()),
+                thumbnail_url: optional_value(field(&columns.thumbnail).as_deref()),
+                image_url: optional_value(field(&columns.image).as_deref()),
+            })
+        })
+        .collect()
+}
+
+/// 任意の項目の値を整える（前後の空白を取り除き、空なら None）
+fn optional_value(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// フィールドの値を文字列に変換する（数値などはそのまま文字列に、null は空文字列にする）
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// YAML の値を JSON の値に変換する（キーが文字列・整数でない要素は無視する）
+fn yaml_to_json(yaml: Yaml) -> Value {
+    match yaml {
+        Yaml::String(s) | Yaml::Real(s) => Value::String(s),
+        Yaml::Integer(i) => Value::from(i),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Array(items) => Value::Array(items.into_iter().map(yaml_to_json).collect()),
+        Yaml::Hash(hash) => Value::Object(
+            hash.into_iter()
+                .filter_map(|(key, value)| {
+                    let key = match key {
+                        Yaml::String(s) => s,
+                        Yaml::Integer(i) => i.to_string(),
+                        _ => return None,
+                    };
+                    Some((key, yaml_to_json(value)))
+                })
+                .collect(),
+        ),
+        Yaml::Null | Yaml::Alias(_) | Yaml::BadValue => Value::Null,
+    }
+}
+
+/// ランダムに侍を1人選ぶ関数
+/// # 引数
+/// * `samurai_entries` - 読み込んだ侍データ
+/// * `rng` - 乱数生成器
+///
+/// # 戻り値
+/// * `Ok(Some(entry))` - ランダムに選ばれた侍データ（返信の整形は呼び出し側で行う）
+/// * `Ok(None)` - 侍データが空の場合
 /// * `Err(e)` - エラーが発生した場合
-pub fn get_samurai_name(samurai_entries: &[SamuraiEntry]) -> Result<Option<String>> {
+pub fn get_samurai_name(
+    samurai_entries: &[SamuraiEntry],
+    rng: &mut impl Rng,
+) -> Result<Option<SamuraiEntry>> {
     // samurai_entries が空でないことを確認
     if samurai_entries.is_empty() {
         error!("Samurai entries are empty");
@@ -101,19 +273,14 @@ pub fn get_samurai_name(samurai_entries: &[SamuraiEntry]) -> Result<Option<Strin
     }
 
     // ランダムな Samurai ID を生成
-    let id = get_random_samurai_id(samurai_entries.len() as u32);
+    let id = get_random_samurai_id(rng, samurai_entries.len() as u32);
 
-    // "Name"列を取得
     if let Some(entry) = samurai_entries.get(id as usize) {
-        let name = &entry.name;
-        let description = &entry.description;
-
-        // name と description を改行コードで結合して返す
         info!(
             "Samurai ID: {}, Name: {}, Description: {}",
-            id, name, description
+            entry.id, entry.name, entry.description
         );
-        Ok(Some(format!("{}: {}\n{}", id, name, description)))
+        Ok(Some(entry.clone()))
     } else {
         error!("Samurai ID {} not found", id);
         Ok(None)
@@ -124,24 +291,187 @@ pub fn get_samurai_name(samurai_entries: &[SamuraiEntry]) -> Result<Option<Strin
 mod tests {
     use super::*;
     use log::error;
-    use std::io::Cursor;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     const SAMPLE_CSV: &str = "S_No.,Name,Description\n1,テスト侍,テストテストテスト\n";
 
+    fn parse_csv(csv: &str) -> Vec<SamuraiEntry> {
+        parse_samurai_dataset(csv, DatasetFormat::Csv, &DatasetColumns::default()).unwrap()
+    }
+
     fn sample_entries() -> Vec<SamuraiEntry> {
-        parse_samurai_reader(Cursor::new(SAMPLE_CSV)).unwrap()
+        parse_csv(SAMPLE_CSV)
+    }
+
+    #[test]
+    fn test_optional_columns() {
+        let csv = "Name,Description,Rarity,Thumbnail,Image\n\
+                   侍A,説明A,SSR,https://example.com/a.png,\n\
+                   侍B,説明B,,,\n";
+        let entries = parse_csv(csv);
+        assert_eq!(entries[0].rarity.as_deref(), Some("SSR"));
+        assert_eq!(
+            entries[0].thumbnail_url.as_deref(),
+            Some("https://example.com/a.png")
+        );
+        assert_eq!(entries[0].image_url, None);
+        assert_eq!(entries[1].rarity, None);
+
+        assert_eq!((entries[0].id, entries[1].id), (0, 1));
+
+        // 任意の列がない CSV でも読み込める
+        assert_eq!(sample_entries()[0].rarity, None);
+    }
+
+    #[test]
+    fn reads_every_dataset_format_with_column_mapping() {
+        let columns = DatasetColumns {
+            name: "名前".to_string(),
+            description: "説明".to_string(),
+            rarity: "レア度".to_string(),
+            ..Default::default()
+        };
+        let cases: &[(DatasetFormat, &str)] = &[
+            (
+                DatasetFormat::Csv,
+                "名前,説明,レア度\n侍A,説明A,SSR\n侍B,説明B,\n",
+            ),
+            (
+                DatasetFormat::Tsv,
+                "名前\t説明\tレア度\n侍A\t説明A\tSSR\n侍B\t説明B\t\n",
+            ),
+            (
+                DatasetFormat::Json,
+                r#"[{"名前": "侍A", "説明": "説明A", "レア度": "SSR"}, {"名前": "侍B", "説明": "説明B"}]"#,
+            ),
+            (
+                DatasetFormat::Json,
+                r#"{"samurai": [{"名前": "侍A", "説明": "説明A", "レア度": "SSR"}, {"名前": "侍B", "説明": "説明B", "レア度": null}]}"#,
+            ),
+            (
+                DatasetFormat::Toml,
+                "[[samurai]]\n\"名前\" = \"侍A\"\n\"説明\" = \"説明A\"\n\"レア度\" = \"SSR\"\n\n\
+                 [[samurai]]\n\"名前\" = \"侍B\"\n\"説明\" = \"説明B\"\n",
+            ),
+            (
+                DatasetFormat::Yaml,
+                "- 名前: 侍A\n  説明: 説明A\n  レア度: SSR\n- 名前: 侍B\n  説明: 説明B\n  レア度: \"\"\n",
+            ),
+            (
+                DatasetFormat::Yaml,
+                "samurai:\n  - { 名前: 侍A, 説明: 説明A, レア度: SSR }\n  - { 名前: 侍B, 説明: 説明B }\n",
+            ),
+        ];
+        for (format, text) in cases {
+            let entries = parse_samurai_dataset(text, *format, &columns)
+                .unwrap_or_else(|e| panic!("{:?}: {:?}", format, e));
+            let summary: Vec<(u32, &str, &str, Option<&str>)> = entries
+                .iter()
+                .map(|e| (e.id, &*e.name, &*e.description, e.rarity.as_deref()))
+                .collect();
+            assert_eq!(
+                summary,
+                vec![(0, "侍A", "説明A", Some("SSR")), (1, "侍B", "説明B", None)],
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn converts_non_string_fields() {
+        let json = r#"[{"Name": 47, "Description": "赤穂浪士", "Rarity": 5}]"#;
+        let entries =
+            parse_samurai_dataset(json, DatasetFormat::Json, &DatasetColumns::default()).unwrap();
+        assert_eq!(entries[0].name, "47");
+        assert_eq!(entries[0].rarity.as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn rejects_datasets_without_required_columns() {
+        let columns = DatasetColumns::default();
+        let cases: &[(DatasetFormat, &str, &str)] = &[
+            (DatasetFormat::Csv, "Name\n侍A\n", "'Description' column"),
+            (
+                DatasetFormat::Tsv,
+                "名前\t説明\n侍A\t説明A\n",
+                "'Name' column",
+            ),
+            (
+                DatasetFormat::Json,
+                r#"[{"Name": "侍A", "Description": "説明A"}, {"Name": "侍B"}]"#,
+                "entry 2 has no 'Description' field",
+            ),
+            (DatasetFormat::Json, r#"{"bushi": []}"#, "'samurai' array"),
+            (DatasetFormat::Json, "[1]", "entry 1 is not a table"),
+            (DatasetFormat::Toml, "Name = \"侍A\"\n", "'samurai' array"),
+            (DatasetFormat::Yaml, "just text\n", "must be an array"),
+            (DatasetFormat::Json, "[", "Failed to parse samurai JSON"),
+        ];
+        for (format, text, expected) in cases {
+            let error = parse_samurai_dataset(text, *format, &columns).unwrap_err();
+            assert!(
+                format!("{:#}", error).contains(expected),
+                "{:?} {:?}: {:#}",
+                format,
+                text,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn detects_format_from_extension() {
+        let cases = [
+            ("samurai.csv", DatasetFormat::Csv),
+            ("samurai.tsv", DatasetFormat::Tsv),
+            ("samurai.JSON", DatasetFormat::Json),
+            ("data/samurai.toml", DatasetFormat::Toml),
+            ("samurai.yaml", DatasetFormat::Yaml),
+            ("samurai.yml", DatasetFormat::Yaml),
+            ("samurai.txt", DatasetFormat::Csv),
+            ("samurai", DatasetFormat::Csv),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(detect_format(Path::new(path)), expected, "{}", path);
+        }
     }
 
     #[test]
     fn test_get_random_samurai_id() {
-        let id = get_random_samurai_id(100);
+        let id = get_random_samurai_id(&mut rand::rng(), 100);
         assert!(id < 100);
     }
 
+    #[test]
+    fn picks_same_ids_for_same_seed() {
+        // (シード, 100件から選ばれる ID の並び)
+        let cases: &[(u64, [u32; 5])] = &[(0, [70, 46, 69, 6, 87]), (42, [68, 95, 42, 62, 28])];
+        for (seed, expected) in cases {
+            let mut rng = ChaCha8Rng::seed_from_u64(*seed);
+            let picks: Vec<u32> = (0..5)
+                .map(|_| get_random_samurai_id(&mut rng, 100))
+                .collect();
+            assert_eq!(picks, expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn picks_exact_entry_for_seed() {
+        let csv = "Name,Description\n侍A,説明A\n侍B,説明B\n侍C,説明C\n";
+        let entries = parse_csv(csv);
+        let mut rng = ChaCha8Rng::seed_from_u64(42);
+        let names: Vec<String> = (0..4)
+            .map(|_| get_samurai_name(&entries, &mut rng).unwrap().unwrap().name)
+            .collect();
+        assert_eq!(names, ["侍C", "侍C", "侍B", "侍B"]);
+    }
+
     #[test]
     fn test_read_samurai_csv_as_vec() {
         let samurai_entries = sample_entries();
-        let name = get_samurai_name(&samurai_entries).unwrap();
+        let name = get_samurai_name(&samurai_entries, &mut rand::rng()).unwrap();
 
         assert!(name.is_some());
     }
@@ -149,11 +479,12 @@ mod tests {
     #[test]
     fn test_get_samurai_name() {
         let samurai_entries = sample_entries();
-        let name = get_samurai_name(&samurai_entries).unwrap();
+        let entry = get_samurai_name(&samurai_entries, &mut rand::rng()).unwrap();
 
-        assert!(name.is_some());
-        if let Some(name) = name {
-            assert!(!name.is_empty());
+        assert!(entry.is_some());
+        if let Some(entry) = entry {
+            assert_eq!(entry, samurai_entries[0]);
+            assert_eq!(entry.name, "テスト侍");
         } else {
             error!("Samurai name is None");
         }
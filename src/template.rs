@@ -0,0 +1,97 @@
+/// 返信テンプレートの `{name}` 形式のプレースホルダーを置き換える関数
+///
+/// `{{` と `}}` はそれぞれ `{`・`}` として出力します。
+/// 未知のプレースホルダーはそのまま残します（設定ミスに気づけるように）。
+/// # 引数
+/// * `template` - テンプレート文字列（例: `"{author}さん、今日の侍は {name} です！"`）
+/// * `vars` - プレースホルダー名と置き換える値の組
+/// # 戻り値
+/// * `String` - 置き換え後の文字列
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('{')
+            && let Some(end) = tail.find('}')
+            && let Some((_, value)) = vars.iter().find(|(key, _)| *key == &tail[1..end])
+        {
+            out.push_str(value);
+            rest = &tail[end + 1..];
+            continue;
+        }
+        out.push_str(&tail[..1]);
+        rest = &tail[1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `@everyone`・`@here`・ユーザー/ロールのメンションを無効化する関数
+///
+/// `@` の直後にゼロ幅スペースを挟み、Discord がメンションとして解釈しないようにします。
+/// # 引数
+/// * `text` - 対象の文字列
+pub fn escape_mentions(text: &str) -> String {
+    text.replace("@everyone", "@\u{200B}everyone")
+        .replace("@here", "@\u{200B}here")
+        .replace("<@", "<@\u{200B}")
+}
+
+/// Discord の Markdown 記法として解釈される文字をエスケープする関数
+/// # 引数
+/// * `text` - 対象の文字列
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_known_placeholders() {
+        let vars = [("author", "太郎"), ("name", "テスト侍")];
+        assert_eq!(
+            render("{author}さん、今日の侍は {name} です！", &vars),
+            "太郎さん、今日の侍は テスト侍 です！"
+        );
+        assert_eq!(render("{name}{name}", &vars), "テスト侍テスト侍");
+    }
+
+    #[test]
+    fn keeps_unknown_placeholders_and_braces() {
+        let vars = [("name", "侍")];
+        assert_eq!(render("{unknown} {name}", &vars), "{unknown} 侍");
+        assert_eq!(render("{{name}} {name", &vars), "{name} {name");
+        assert_eq!(render("}{", &vars), "}{");
+    }
+
+    #[test]
+    fn escapes_mentions() {
+        assert_eq!(
+            escape_mentions("@everyone @here <@123> <@&456>"),
+            "@\u{200B}everyone @\u{200B}here <@\u{200B}123> <@\u{200B}&456>"
+        );
+        assert_eq!(escape_mentions("a@example.com"), "a@example.com");
+    }
+
+    #[test]
+    fn escapes_markdown() {
+        assert_eq!(escape_markdown("__侍__ *x* `y`"), r"\_\_侍\_\_ \*x\* \`y\`");
+        assert_eq!(escape_markdown("普通の名前"), "普通の名前");
+    }
+}